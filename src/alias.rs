@@ -0,0 +1,128 @@
+use heapless::Vec;
+
+use crate::error::CapacityError;
+
+/// Outcome of [`AliasTable::resolve`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    /// The name stands for this command
+    Found(&'static str),
+    /// The name is a prefix of several commands
+    Ambiguous,
+    /// Neither an alias nor a prefix of any command
+    Unknown,
+}
+
+/// Fixed-capacity table of command aliases
+///
+/// Holds up to `N` `(alias, command)` pairs, e.g. `ls` for `list`. Besides
+/// exact aliases, [`resolve`](Self::resolve) accepts any unambiguous prefix
+/// of a known command.
+pub struct AliasTable<const N: usize> {
+    aliases: Vec<(&'static str, &'static str), N>,
+}
+
+impl<const N: usize> AliasTable<N> {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self { aliases: Vec::new() }
+    }
+
+    /// Make `alias` stand for `command`
+    ///
+    /// Adding an alias again replaces its command. Fails if the table is
+    /// full.
+    pub fn add(&mut self, alias: &'static str, command: &'static str) -> Result<(), CapacityError> {
+        if let Some(entry) = self.aliases.iter_mut().find(|(a, _)| *a == alias) {
+            entry.1 = command;
+            return Ok(());
+        }
+        self.aliases.push((alias, command)).map_err(|_| CapacityError)
+    }
+
+    /// Get the command an alias stands for
+    pub fn get(&self, alias: &str) -> Option<&'static str> {
+        self.aliases
+            .iter()
+            .find(|(a, _)| *a == alias)
+            .map(|(_, command)| *command)
+    }
+
+    /// Get the number of aliases
+    pub fn len(&self) -> usize {
+        self.aliases.len()
+    }
+
+    /// Check if no aliases are defined
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// Find the command `name` refers to
+    ///
+    /// An exact alias wins, then an exact command name. Otherwise `name` must
+    /// be the prefix of exactly one of `commands`, such as the names of a
+    /// [`CommandRegistry`](crate::CommandRegistry).
+    pub fn resolve<I>(&self, name: &str, commands: I) -> Resolution
+    where
+        I: IntoIterator<Item = &'static str>,
+    {
+        if let Some(command) = self.get(name) {
+            return Resolution::Found(command);
+        }
+        if name.is_empty() {
+            return Resolution::Unknown;
+        }
+        let mut resolution = Resolution::Unknown;
+        for command in commands {
+            if command == name {
+                return Resolution::Found(command);
+            }
+            if command.starts_with(name) {
+                resolution = match resolution {
+                    Resolution::Unknown => Resolution::Found(command),
+                    _ => Resolution::Ambiguous,
+                };
+            }
+        }
+        resolution
+    }
+}
+
+impl<const N: usize> Default for AliasTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMANDS: [&str; 4] = ["list", "restart", "reset", "re"];
+
+    #[test]
+    fn test_resolve_alias_and_prefix() {
+        let mut aliases = AliasTable::<4>::new();
+        aliases.add("ls", "list").unwrap();
+        assert_eq!(aliases.resolve("ls", COMMANDS), Resolution::Found("list"));
+        assert_eq!(aliases.resolve("li", COMMANDS), Resolution::Found("list"));
+        assert_eq!(aliases.resolve("rest", COMMANDS), Resolution::Found("restart"));
+        assert_eq!(aliases.resolve("res", COMMANDS), Resolution::Ambiguous);
+        // An exact name is never ambiguous, even if it prefixes others
+        assert_eq!(aliases.resolve("re", COMMANDS), Resolution::Found("re"));
+        assert_eq!(aliases.resolve("halt", COMMANDS), Resolution::Unknown);
+        assert_eq!(aliases.resolve("", COMMANDS), Resolution::Unknown);
+    }
+
+    #[test]
+    fn test_add_capacity_and_replace() {
+        let mut aliases = AliasTable::<1>::new();
+        assert!(aliases.is_empty());
+        aliases.add("ls", "list").unwrap();
+        assert_eq!(aliases.add("q", "quit"), Err(CapacityError));
+        aliases.add("ls", "reset").unwrap();
+        assert_eq!(aliases.get("ls"), Some("reset"));
+        assert_eq!(aliases.len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::terminal::KeyCode;
+
+/// Number of remappable bytes: the C0 control range plus DEL
+const SLOTS: usize = 33;
+
+/// Table mapping control bytes to the keys they produce
+///
+/// [`Terminal::process_byte`](crate::Terminal::process_byte) looks up every
+/// byte in `0x00..=0x1F` and `0x7F` here. Unbound bytes are dropped. ESC
+/// (`0x1B`) always starts an escape sequence and cannot be rebound.
+///
+/// The default table binds Ctrl+letter bytes without a dedicated key to
+/// [`KeyCode::Ctrl`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBindings {
+    keys: [Option<KeyCode>; SLOTS],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut bindings = Self::empty();
+        bindings.bind(b'\r', Some(KeyCode::Enter));
+        bindings.bind(b'\n', Some(KeyCode::Enter));
+        bindings.bind(0x08, Some(KeyCode::Backspace));
+        bindings.bind(0x7F, Some(KeyCode::Backspace));
+        bindings.bind(0x03, Some(KeyCode::CtrlC));
+        bindings.bind(0x04, Some(KeyCode::CtrlD));
+        bindings.bind(0x09, Some(KeyCode::Tab));
+        bindings.bind(0x14, Some(KeyCode::Transpose));
+        bindings.bind(0x17, Some(KeyCode::KillWord));
+        bindings.bind(0x15, Some(KeyCode::KillToStart));
+        bindings.bind(0x0B, Some(KeyCode::KillToEnd));
+        bindings.bind(0x19, Some(KeyCode::Yank));
+        bindings.bind(0x1F, Some(KeyCode::Undo));
+        bindings.bind(0x1E, Some(KeyCode::Redo));
+        // Remaining Ctrl+letter bytes are reported for the application
+        for byte in 0x01..=0x1A {
+            if bindings.get(byte).is_none() {
+                bindings.bind(byte, Some(KeyCode::Ctrl(b'a' + byte - 1)));
+            }
+        }
+        bindings
+    }
+}
+
+impl KeyBindings {
+    /// Create a table with no bindings at all
+    pub fn empty() -> Self {
+        Self { keys: [None; SLOTS] }
+    }
+
+    /// Bind a control byte to a key, or unbind it with `None`
+    ///
+    /// Bytes outside the control range and ESC are ignored. Use
+    /// [`KeyCode::Custom`] for application-defined actions.
+    pub fn bind(&mut self, byte: u8, key: Option<KeyCode>) {
+        if let Some(slot) = slot(byte) {
+            self.keys[slot] = key;
+        }
+    }
+
+    /// Get the key bound to a control byte
+    pub fn get(&self, byte: u8) -> Option<KeyCode> {
+        slot(byte).and_then(|slot| self.keys[slot])
+    }
+}
+
+/// Position of a remappable byte in the table
+fn slot(byte: u8) -> Option<usize> {
+    match byte {
+        0x1B => None,
+        0x00..=0x1F => Some(byte as usize),
+        0x7F => Some(SLOTS - 1),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bindings() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.get(b'\r'), Some(KeyCode::Enter));
+        assert_eq!(bindings.get(0x7F), Some(KeyCode::Backspace));
+        assert_eq!(bindings.get(0x07), Some(KeyCode::Ctrl(b'g')));
+        assert_eq!(bindings.get(0x0E), Some(KeyCode::Ctrl(b'n')));
+        assert_eq!(bindings.get(0x1C), None);
+        assert_eq!(bindings.get(b'a'), None);
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind(0x03, None);
+        bindings.bind(0x07, Some(KeyCode::Custom(7)));
+        bindings.bind(0x1B, Some(KeyCode::Enter));
+        bindings.bind(b'a', Some(KeyCode::Enter));
+        assert_eq!(bindings.get(0x03), None);
+        assert_eq!(bindings.get(0x07), Some(KeyCode::Custom(7)));
+        assert_eq!(bindings.get(0x1B), None);
+        assert_eq!(bindings.get(b'a'), None);
+    }
+}
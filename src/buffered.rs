@@ -0,0 +1,102 @@
+//! Write adapter that coalesces small writes into larger bursts.
+
+use embedded_io_async::{ErrorType, Write as AsyncWrite};
+use heapless::Vec;
+
+/// Writer collecting output in an `N` byte buffer
+///
+/// Writes only reach the inner writer when the buffer is full or on
+/// [`flush`](AsyncWrite::flush), so e.g. a colored message becomes a single
+/// burst instead of one transfer per escape code. Wrap it in a
+/// [`TerminalWriter`](crate::TerminalWriter) created
+/// [`with_auto_flush(false)`](crate::TerminalWriter::with_auto_flush) and
+/// call [`TerminalWriter::flush`](crate::TerminalWriter::flush) when done.
+///
+/// Nothing is written on drop, as that would need an async drop: output
+/// still buffered when the writer goes away is lost.
+pub struct BufferedWriter<'a, W: AsyncWrite, const N: usize> {
+    writer: &'a mut W,
+    buffer: Vec<u8, N>,
+}
+
+impl<'a, W: AsyncWrite, const N: usize> BufferedWriter<'a, W, N> {
+    /// Create a buffered writer with an empty buffer
+    pub fn new(writer: &'a mut W) -> Self {
+        Self {
+            writer,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Get the number of bytes waiting to be written
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Pass the buffered bytes on to the inner writer without flushing it
+    async fn drain(&mut self) -> Result<(), W::Error> {
+        self.writer.write_all(&self.buffer).await?;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: AsyncWrite, const N: usize> ErrorType for BufferedWriter<'_, W, N> {
+    type Error = W::Error;
+}
+
+impl<W: AsyncWrite, const N: usize> AsyncWrite for BufferedWriter<'_, W, N> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if N == 0 {
+            return self.writer.write(buf).await;
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.buffer.is_full() {
+            self.drain().await?;
+        }
+        let len = buf.len().min(N - self.buffer.len());
+        // Cannot fail, the length is capped at the free space
+        let _ = self.buffer.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.drain().await?;
+        self.writer.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWriter;
+    use crate::TerminalWriter;
+    use embassy_futures::block_on;
+
+    #[test]
+    fn test_colored_message_is_one_write() {
+        let mut output = MockWriter::new();
+        {
+            let mut buffered = BufferedWriter::<_, 64>::new(&mut output);
+            let mut writer = TerminalWriter::new(&mut buffered, true).with_auto_flush(false);
+            block_on(writer.write_error("failed")).unwrap();
+            block_on(writer.flush()).unwrap();
+        }
+        assert_eq!(output.as_str(), "\x1b[31mfailed\x1b[0m");
+        assert_eq!(output.writes, 1);
+    }
+
+    #[test]
+    fn test_full_buffer_is_passed_on() {
+        let mut output = MockWriter::new();
+        let mut buffered = BufferedWriter::<_, 4>::new(&mut output);
+        block_on(buffered.write_all(b"abcdef")).unwrap();
+        assert_eq!(buffered.buffered(), 2);
+        block_on(buffered.flush()).unwrap();
+        assert_eq!(buffered.buffered(), 0);
+        assert_eq!(output.as_str(), "abcdef");
+        assert_eq!(output.writes, 2);
+    }
+}
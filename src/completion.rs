@@ -0,0 +1,191 @@
+use embedded_io_async::Write as AsyncWrite;
+use heapless::Vec;
+
+use crate::writer::TerminalWriter;
+use crate::width::display_width;
+
+/// Maximum number of candidates considered for a single completion
+pub const MAX_CANDIDATES: usize = 16;
+
+/// Candidate words collected for a completion
+pub type Candidates = Vec<&'static str, MAX_CANDIDATES>;
+
+/// Callback providing the words that may appear at the cursor
+///
+/// `context` is the part of the line before the word being completed, so the
+/// callback can offer e.g. command names for the first word and arguments
+/// afterwards. It should push every word valid at that position; filtering
+/// by the typed prefix is done by the terminal.
+pub type CompleterFn = fn(context: &str, candidates: &mut Candidates);
+
+/// How multiple completion candidates are listed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionLayout {
+    /// Row-major grid sized to the terminal width
+    Grid,
+    /// One candidate per line
+    List,
+}
+
+/// Configuration for tab completion
+#[derive(Clone, Copy)]
+pub struct CompletionConfig {
+    /// Source of candidates; completion is disabled when `None`
+    pub completer: Option<CompleterFn>,
+    /// Layout used when listing several candidates
+    pub layout: CompletionLayout,
+    /// Match the typed prefix using ASCII case folding
+    ///
+    /// The inserted text always keeps the candidate's own case.
+    pub ignore_case: bool,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            completer: None,
+            layout: CompletionLayout::Grid,
+            ignore_case: false,
+        }
+    }
+}
+
+impl CompletionConfig {
+    /// Check whether `candidate` completes the typed `prefix`
+    pub fn matches(&self, candidate: &str, prefix: &str) -> bool {
+        if self.ignore_case {
+            candidate
+                .as_bytes()
+                .get(..prefix.len())
+                .is_some_and(|head| head.eq_ignore_ascii_case(prefix.as_bytes()))
+        } else {
+            candidate.starts_with(prefix)
+        }
+    }
+}
+
+/// Get the longest prefix shared by all candidates
+///
+/// Comparison is byte-wise; the result is always cut at a character boundary.
+/// An empty candidate list yields an empty prefix.
+pub fn common_prefix<'a>(candidates: &[&'a str]) -> &'a str {
+    let Some((first, rest)) = candidates.split_first() else {
+        return "";
+    };
+    let mut len = first.len();
+    for candidate in rest {
+        len = first
+            .bytes()
+            .zip(candidate.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    while !first.is_char_boundary(len) {
+        len -= 1;
+    }
+    &first[..len]
+}
+
+/// Write a list of candidates, one row per line
+///
+/// With [`CompletionLayout::Grid`] and a known `width`, candidates are laid out
+/// row-major in as many columns as fit, each column as wide as the longest
+/// candidate plus two spaces. Otherwise each candidate gets its own line.
+pub async fn write_candidates<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    candidates: &[&str],
+    layout: CompletionLayout,
+    width: Option<u16>,
+) -> Result<(), W::Error> {
+    let col_width = candidates.iter().map(|c| display_width(c)).max().unwrap_or(0) + 2;
+    let columns = match (layout, width) {
+        (CompletionLayout::Grid, Some(width)) => (width as usize / col_width).max(1),
+        _ => 1,
+    };
+
+    for row in candidates.chunks(columns) {
+        for (i, candidate) in row.iter().enumerate() {
+            writer.write_str(candidate).await?;
+            if i + 1 < row.len() {
+                write_padding(writer, col_width - display_width(candidate)).await?;
+            }
+        }
+        writer.write_str("\r\n").await?;
+    }
+    Ok(())
+}
+
+/// Write `n` spaces
+async fn write_padding<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    mut n: usize,
+) -> Result<(), W::Error> {
+    const SPACES: &str = "                ";
+    while n > 0 {
+        let chunk = n.min(SPACES.len());
+        writer.write_str(&SPACES[..chunk]).await?;
+        n -= chunk;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWriter;
+    use embassy_futures::block_on;
+
+    const WORDS: &[&str] = &["help", "hello", "history", "halt", "hi"];
+
+    #[test]
+    fn test_grid_layout() {
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        // Columns are 9 wide, so three fit into 30 characters
+        block_on(write_candidates(&mut writer, WORDS, CompletionLayout::Grid, Some(30))).unwrap();
+        assert_eq!(
+            output.as_str(),
+            "help     hello    history\r\nhalt     hi\r\n"
+        );
+    }
+
+    #[test]
+    fn test_list_fallback_without_width() {
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        block_on(write_candidates(&mut writer, &WORDS[..3], CompletionLayout::Grid, None)).unwrap();
+        assert_eq!(output.as_str(), "help\r\nhello\r\nhistory\r\n");
+    }
+
+    #[test]
+    fn test_matches_ignore_case() {
+        let mut config = CompletionConfig::default();
+        assert!(config.matches("help", "he"));
+        assert!(!config.matches("Help", "he"));
+
+        config.ignore_case = true;
+        assert!(config.matches("Help", "he"));
+        assert!(config.matches("HELLO", "hE"));
+        assert!(!config.matches("halt", "he"));
+        assert!(!config.matches("h", "he"));
+    }
+
+    #[test]
+    fn test_common_prefix_full_match() {
+        assert_eq!(common_prefix(&["help", "help"]), "help");
+        assert_eq!(common_prefix(&["reboot"]), "reboot");
+    }
+
+    #[test]
+    fn test_common_prefix_partial() {
+        assert_eq!(common_prefix(&["help", "hello", "helium"]), "hel");
+        assert_eq!(common_prefix(&["hello", "help"]), "hel");
+    }
+
+    #[test]
+    fn test_common_prefix_none() {
+        assert_eq!(common_prefix(&["help", "reboot"]), "");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
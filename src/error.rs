@@ -0,0 +1,13 @@
+use core::fmt;
+
+/// Error returned when an item does not fit into a fixed-capacity container
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapacityError;
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("capacity exceeded")
+    }
+}
+
+impl core::error::Error for CapacityError {}
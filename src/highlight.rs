@@ -0,0 +1,98 @@
+use core::ops::Range;
+
+use embedded_io_async::Write as AsyncWrite;
+use heapless::Vec;
+
+use crate::writer::TerminalWriter;
+
+/// Maximum number of colored ranges in one line
+pub const MAX_HIGHLIGHTS: usize = 8;
+
+/// A colored part of the input line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Highlight {
+    /// Byte range within the line
+    pub range: Range<usize>,
+    /// Color as accepted by [`TerminalWriter::set_color`]
+    pub color: u8,
+}
+
+/// Colored ranges collected for a line
+pub type Highlights = Vec<Highlight, MAX_HIGHLIGHTS>;
+
+/// Callback choosing colors for the input line whenever it is redrawn
+///
+/// It should push the ranges of `line` to color, in order and without
+/// overlaps; text outside any range is written plainly. Ranges that break
+/// these rules or split a character are written plainly as well.
+pub type HighlighterFn = fn(line: &str, highlights: &mut Highlights);
+
+/// Write `line` with the colors chosen by `highlighter`
+///
+/// Only color escape codes are added, so the cursor ends up in the same
+/// column as after writing the plain line.
+pub async fn write_highlighted<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    line: &str,
+    highlighter: HighlighterFn,
+) -> Result<(), W::Error> {
+    let mut highlights = Highlights::new();
+    highlighter(line, &mut highlights);
+
+    let mut pos = 0;
+    for highlight in &highlights {
+        let (Some(plain), Some(colored)) = (
+            line.get(pos..highlight.range.start),
+            line.get(highlight.range.clone()),
+        ) else {
+            continue;
+        };
+        writer.write_str(plain).await?;
+        writer.write_colored(colored, highlight.color).await?;
+        pos = highlight.range.end;
+    }
+    writer.write_str(&line[pos..]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWriter;
+    use crate::writer::colors;
+    use embassy_futures::block_on;
+
+    fn command_and_flags(line: &str, highlights: &mut Highlights) {
+        let name_end = line.find(' ').unwrap_or(line.len());
+        let _ = highlights.push(Highlight {
+            range: 0..name_end,
+            color: colors::GREEN,
+        });
+        if let Some(start) = line.find(" -") {
+            let end = line[start + 1..].find(' ').map_or(line.len(), |i| start + 1 + i);
+            let _ = highlights.push(Highlight {
+                range: start + 1..end,
+                color: colors::YELLOW,
+            });
+        }
+    }
+
+    #[test]
+    fn test_write_highlighted() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(write_highlighted(&mut writer, "ls -l dir", command_and_flags)).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[32mls\x1b[0m \x1b[33m-l\x1b[0m dir");
+    }
+
+    #[test]
+    fn test_invalid_ranges_are_plain() {
+        fn broken(_: &str, highlights: &mut Highlights) {
+            let _ = highlights.push(Highlight { range: 2..40, color: 1 });
+            let _ = highlights.push(Highlight { range: 1..2, color: 2 });
+        }
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(write_highlighted(&mut writer, "abc", broken)).unwrap();
+        assert_eq!(mock.as_str(), "a\x1b[32mb\x1b[0mc");
+    }
+}
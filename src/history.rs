@@ -1,26 +1,71 @@
+use embedded_io_async::Write as AsyncWrite;
 use heapless::{String, Vec};
 
+use crate::error::CapacityError;
+use crate::writer::{TerminalWriter, WriteFmtError};
+
 /// Configuration for command history
 #[derive(Clone, Copy)]
 pub struct HistoryConfig {
     /// Maximum number of history entries
     pub max_entries: usize,
-    /// Whether to deduplicate consecutive identical commands
-    pub deduplicate: bool,
+    /// Which identical commands are stored only once
+    pub deduplicate: DedupMode,
+}
+
+/// How [`History`] treats a command that is already stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DedupMode {
+    /// Store every command
+    None,
+    /// Skip a command identical to the previous one
+    Consecutive,
+    /// Remove any earlier identical entry, so each command appears once at
+    /// its most recent position
+    All,
 }
 
 impl Default for HistoryConfig {
     fn default() -> Self {
         Self {
             max_entries: 10,
-            deduplicate: true,
+            deduplicate: DedupMode::Consecutive,
         }
     }
 }
 
+impl HistoryConfig {
+    /// Write the configuration as a human-readable list, one field per line
+    pub async fn write_config<W: AsyncWrite>(
+        &self,
+        writer: &mut TerminalWriter<'_, W>,
+    ) -> Result<(), WriteFmtError<W::Error>> {
+        writer.write_fmt(format_args!("max_entries:  {}\r\n", self.max_entries)).await?;
+        writer.write_fmt(format_args!("deduplicate:  {:?}\r\n", self.deduplicate)).await
+    }
+}
+
+/// Asynchronous backend for persisting command history
+///
+/// Lets history be written straight to e.g. external flash through an async
+/// driver, without serializing it into an intermediate buffer first.
+#[allow(async_fn_in_trait)]
+pub trait HistoryStorage<const BUF_SIZE: usize> {
+    /// Error reported by the storage driver
+    type Error;
+
+    /// Store `entries` (oldest first), replacing anything saved before
+    async fn save(&mut self, entries: &[String<BUF_SIZE>]) -> Result<(), Self::Error>;
+
+    /// Load saved entries into `history`, oldest first, using [`History::add`]
+    async fn load(&mut self, history: &mut History<BUF_SIZE>) -> Result<(), Self::Error>;
+}
+
 /// Command history manager
 pub struct History<const BUF_SIZE: usize> {
     entries: Vec<String<BUF_SIZE>, 16>,
+    /// Time each entry in `entries` was added at
+    times: Vec<u64, 16>,
     config: HistoryConfig,
     current_index: Option<usize>,
 }
@@ -30,36 +75,63 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
     pub fn new(config: HistoryConfig) -> Self {
         Self {
             entries: Vec::new(),
+            times: Vec::new(),
             config,
             current_index: None,
         }
     }
 
     /// Add a command to history
+    ///
+    /// The entry gets timestamp 0, see [`History::add_at`].
+    #[allow(clippy::result_unit_err)]
     pub fn add(&mut self, command: &str) -> Result<(), ()> {
+        self.add_at(command, 0).map_err(|_| ())
+    }
+
+    /// Add a command to history, tagged with the time it ran
+    ///
+    /// `time` is any tick count from the caller's clock, e.g.
+    /// `Instant::now().as_ticks()`. A command skipped as a consecutive
+    /// duplicate keeps the time of the earlier entry.
+    pub fn add_at(&mut self, command: &str, time: u64) -> Result<(), CapacityError> {
+        // Submitting always ends navigation, even if nothing gets stored
+        self.current_index = None;
+
         // Skip empty commands
         if command.trim().is_empty() {
             return Ok(());
         }
 
         // Check for deduplication
-        if self.config.deduplicate {
-            if let Some(last) = self.entries.last() {
-                if last.as_str() == command {
-                    return Ok(());
+        match self.config.deduplicate {
+            DedupMode::None => {}
+            DedupMode::Consecutive => {
+                if let Some(last) = self.entries.last() {
+                    if last.as_str() == command {
+                        return Ok(());
+                    }
+                }
+            }
+            DedupMode::All => {
+                if let Some(index) = self.entries.iter().position(|e| e.as_str() == command) {
+                    self.entries.remove(index);
+                    self.times.remove(index);
                 }
             }
         }
 
-        let entry = String::try_from(command).map_err(|_| ())?;
+        let entry = String::try_from(command).map_err(|_| CapacityError)?;
 
         // If at capacity, remove oldest
         if self.entries.len() >= self.config.max_entries {
             self.entries.remove(0);
+            self.times.remove(0);
         }
 
-        self.entries.push(entry).map_err(|_| ())?;
-        self.current_index = None;
+        self.entries.push(entry).map_err(|_| CapacityError)?;
+        // Cannot fail, `times` holds as many items as `entries`
+        let _ = self.times.push(time);
         Ok(())
     }
 
@@ -80,6 +152,7 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
     }
 
     /// Get the next command in history
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<&str> {
         match self.current_index {
             None => None,
@@ -94,6 +167,40 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
         }
     }
 
+    /// Get the previous command starting with `prefix`
+    ///
+    /// Like [`History::previous`], but skips entries not matching. Returns
+    /// `None` without moving if there is no older match.
+    pub fn previous_with_prefix(&mut self, prefix: &str) -> Option<&str> {
+        let end = self.current_index.unwrap_or(self.entries.len());
+        let index = self.entries[..end].iter().rposition(|e| e.starts_with(prefix))?;
+        self.current_index = Some(index);
+        Some(&self.entries[index])
+    }
+
+    /// Get the next command starting with `prefix`
+    ///
+    /// Like [`History::next`], but skips entries not matching. Walking past
+    /// the newest match ends navigation and returns `None`.
+    pub fn next_with_prefix(&mut self, prefix: &str) -> Option<&str> {
+        let start = self.current_index? + 1;
+        match self.entries[start..].iter().position(|e| e.starts_with(prefix)) {
+            Some(offset) => {
+                self.current_index = Some(start + offset);
+                Some(&self.entries[start + offset])
+            }
+            None => {
+                self.current_index = None;
+                None
+            }
+        }
+    }
+
+    /// Get the index of the entry being navigated to, if any
+    pub fn position(&self) -> Option<usize> {
+        self.current_index
+    }
+
     /// Reset the history navigation position
     pub fn reset_position(&mut self) {
         self.current_index = None;
@@ -104,6 +211,42 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
         self.entries.len()
     }
 
+    /// Get the entry at `index`, counting from the oldest at 0
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|s| s.as_str())
+    }
+
+    /// Get the newest entry
+    pub fn last(&self) -> Option<&str> {
+        self.entries.last().map(|s| s.as_str())
+    }
+
+    /// Resolve a shell-style history reference
+    ///
+    /// `!!` is the newest entry, `!N` entry N counting from 1 for the oldest
+    /// and `!-N` the Nth newest. Returns `None` if `token` is not such a
+    /// reference or no entry matches.
+    pub fn expand(&self, token: &str) -> Option<String<BUF_SIZE>> {
+        let reference = token.strip_prefix('!')?;
+        // Unlike `str::parse`, no sign is accepted
+        let number = |digits: &str| {
+            if digits.bytes().all(|b| b.is_ascii_digit()) {
+                digits.parse::<usize>().ok()
+            } else {
+                None
+            }
+        };
+        let index = if reference == "!" {
+            self.entries.len().checked_sub(1)?
+        } else if let Some(back) = reference.strip_prefix('-') {
+            let back = number(back)?;
+            self.entries.len().checked_sub(back.checked_sub(1)? + 1)?
+        } else {
+            number(reference)?.checked_sub(1)?
+        };
+        self.entries.get(index).cloned()
+    }
+
     /// Check if history is empty
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
@@ -112,6 +255,7 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
     /// Clear all history
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.times.clear();
         self.current_index = None;
     }
 
@@ -120,15 +264,63 @@ impl<const BUF_SIZE: usize> History<BUF_SIZE> {
         self.entries.iter().map(|s| s.as_str())
     }
 
+    /// Get an iterator over history entries and the times they were added
+    /// at (oldest to newest)
+    pub fn iter_with_time(&self) -> impl Iterator<Item = (&str, u64)> {
+        self.entries.iter().map(|s| s.as_str()).zip(self.times.iter().copied())
+    }
+
     /// Get an iterator over history entries in reverse (newest to oldest)
     pub fn iter_rev(&self) -> impl Iterator<Item = &str> {
         self.entries.iter().rev().map(|s| s.as_str())
     }
+
+    /// Save all entries to a storage backend
+    pub async fn persist<S: HistoryStorage<BUF_SIZE>>(
+        &self,
+        storage: &mut S,
+    ) -> Result<(), S::Error> {
+        storage.save(&self.entries).await
+    }
+
+    /// Replace the current entries with those loaded from a storage backend
+    pub async fn restore<S: HistoryStorage<BUF_SIZE>>(
+        &mut self,
+        storage: &mut S,
+    ) -> Result<(), S::Error> {
+        self.clear();
+        storage.load(self).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::MockWriter;
+    use embassy_futures::block_on;
+
+    /// Storage that keeps the saved entries in RAM
+    struct MockStorage {
+        saved: Vec<String<64>, 16>,
+        saves: usize,
+    }
+
+    impl HistoryStorage<64> for MockStorage {
+        type Error = ();
+
+        async fn save(&mut self, entries: &[String<64>]) -> Result<(), ()> {
+            self.saved = entries.iter().cloned().collect();
+            self.saves += 1;
+            Ok(())
+        }
+
+        async fn load(&mut self, history: &mut History<64>) -> Result<(), ()> {
+            for entry in &self.saved {
+                history.add(entry)?;
+            }
+            Ok(())
+        }
+    }
 
     #[test]
     fn test_history_add() {
@@ -141,12 +333,38 @@ mod tests {
     #[test]
     fn test_history_deduplicate() {
         let mut history = History::<64>::new(HistoryConfig {
-            deduplicate: true,
+            deduplicate: DedupMode::Consecutive,
             ..Default::default()
         });
         history.add("command1").unwrap();
         history.add("command1").unwrap();
         assert_eq!(history.len(), 1);
+        history.add("command2").unwrap();
+        history.add("command1").unwrap();
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn test_history_deduplicate_all() {
+        let mut history = History::<64>::new(HistoryConfig {
+            deduplicate: DedupMode::All,
+            ..Default::default()
+        });
+        for command in ["a", "b", "a"] {
+            history.add(command).unwrap();
+        }
+        assert!(history.iter().eq(["b", "a"]));
+    }
+
+    #[test]
+    fn test_history_no_deduplicate() {
+        let mut history = History::<64>::new(HistoryConfig {
+            deduplicate: DedupMode::None,
+            ..Default::default()
+        });
+        history.add("a").unwrap();
+        history.add("a").unwrap();
+        assert_eq!(history.len(), 2);
     }
 
     #[test]
@@ -161,4 +379,102 @@ mod tests {
         assert_eq!(history.next(), Some("cmd3"));
         assert_eq!(history.next(), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_history_prefix_search() {
+        let mut history = History::<64>::new(HistoryConfig::default());
+        for command in ["git status", "ls", "git log", "make"] {
+            history.add(command).unwrap();
+        }
+        assert_eq!(history.previous_with_prefix("git "), Some("git log"));
+        assert_eq!(history.previous_with_prefix("git "), Some("git status"));
+        assert_eq!(history.previous_with_prefix("git "), None);
+        assert_eq!(history.position(), Some(0));
+
+        assert_eq!(history.next_with_prefix("git "), Some("git log"));
+        assert_eq!(history.next_with_prefix("git "), None);
+        assert_eq!(history.position(), None);
+    }
+
+    #[test]
+    fn test_history_duplicate_submit_resets_navigation() {
+        let mut history = History::<64>::new(HistoryConfig::default());
+        history.add("cmd1").unwrap();
+        history.add("cmd2").unwrap();
+
+        assert_eq!(history.previous(), Some("cmd2"));
+        assert_eq!(history.previous(), Some("cmd1"));
+
+        // Re-submitting the newest entry is deduplicated but must still reset
+        history.add("cmd2").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.previous(), Some("cmd2"));
+    }
+
+    #[test]
+    fn test_history_get() {
+        let mut history = History::<64>::new(HistoryConfig::default());
+        assert_eq!(history.last(), None);
+        history.add("cmd1").unwrap();
+        history.add("cmd2").unwrap();
+        assert_eq!(history.get(0), Some("cmd1"));
+        assert_eq!(history.get(1), Some("cmd2"));
+        assert_eq!(history.get(2), None);
+        assert_eq!(history.last(), Some("cmd2"));
+    }
+
+    #[test]
+    fn test_history_expand() {
+        let mut history = History::<64>::new(HistoryConfig::default());
+        assert_eq!(history.expand("!!"), None);
+        for command in ["ls", "make", "reboot"] {
+            history.add(command).unwrap();
+        }
+        let expand = |token| history.expand(token);
+        assert_eq!(expand("!!").as_deref(), Some("reboot"));
+        assert_eq!(expand("!1").as_deref(), Some("ls"));
+        assert_eq!(expand("!3").as_deref(), Some("reboot"));
+        assert_eq!(expand("!-1").as_deref(), Some("reboot"));
+        assert_eq!(expand("!-3").as_deref(), Some("ls"));
+        for token in ["!0", "!4", "!-0", "!-4", "!x", "!+1", "!", "1", "!!!"] {
+            assert_eq!(expand(token), None, "{}", token);
+        }
+    }
+
+    #[test]
+    fn test_history_timestamps() {
+        let mut history = History::<64>::new(HistoryConfig {
+            max_entries: 2,
+            deduplicate: DedupMode::All,
+        });
+        history.add_at("a", 10).unwrap();
+        history.add("b").unwrap();
+        history.add_at("c", 30).unwrap();
+        history.add_at("b", 40).unwrap();
+        assert!(history.iter_with_time().eq([("c", 30), ("b", 40)]));
+    }
+
+    #[test]
+    fn test_write_config() {
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        embassy_futures::block_on(HistoryConfig::default().write_config(&mut writer)).unwrap();
+        assert_eq!(output.as_str(), "max_entries:  10\r\ndeduplicate:  Consecutive\r\n");
+    }
+
+    #[test]
+    fn test_persist_and_restore() {
+        let mut storage = MockStorage { saved: Vec::new(), saves: 0 };
+        let mut history = History::<64>::new(HistoryConfig::default());
+        history.add("first").unwrap();
+        history.add("second").unwrap();
+        block_on(history.persist(&mut storage)).unwrap();
+        assert_eq!(storage.saves, 1);
+
+        let mut restored = History::<64>::new(HistoryConfig::default());
+        restored.add("stale").unwrap();
+        block_on(restored.restore(&mut storage)).unwrap();
+        assert!(restored.iter().eq(["first", "second"]));
+        assert_eq!(restored.previous(), Some("second"));
+    }
+}
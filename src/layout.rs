@@ -0,0 +1,119 @@
+use embedded_io_async::Write as AsyncWrite;
+
+use crate::writer::TerminalWriter;
+
+/// Chat-style screen layout with a scrolling message area and a pinned input line
+///
+/// The top `rows - 1` lines form an ANSI scroll region that messages are
+/// appended to, while the last row is reserved for the prompt and input.
+/// Messages are written with the cursor saved and restored, so an in-progress
+/// edit on the input line is not disturbed.
+///
+/// Without ANSI support the layout degrades to plain line-by-line output.
+pub struct SplitLayout<'a, W: AsyncWrite> {
+    writer: TerminalWriter<'a, W>,
+    rows: u16,
+}
+
+impl<'a, W: AsyncWrite> SplitLayout<'a, W> {
+    /// Create a layout for a terminal with `rows` lines
+    pub fn new(writer: TerminalWriter<'a, W>, rows: u16) -> Self {
+        Self {
+            writer,
+            rows: rows.max(2),
+        }
+    }
+
+    /// Get the underlying writer, e.g. to pass it to `read_line`
+    pub fn writer(&mut self) -> &mut TerminalWriter<'a, W> {
+        &mut self.writer
+    }
+
+    /// Clear the screen, set up the scroll region and move to the input line
+    pub async fn init(&mut self) -> Result<(), W::Error> {
+        if !self.writer.ansi_enabled() {
+            return Ok(());
+        }
+        self.writer.clear_screen().await?;
+        self.writer.set_scroll_region(1, self.rows - 1).await?;
+        self.move_to_row(self.rows).await
+    }
+
+    /// Append a message to the bottom of the scrollback region
+    pub async fn push_message(&mut self, text: &str) -> Result<(), W::Error> {
+        if !self.writer.ansi_enabled() {
+            return self.writer.writeln(text).await;
+        }
+        // Write at the bottom of the scroll region so the newline scrolls
+        // older messages up, then return to the input line
+        self.writer.save_cursor().await?;
+        self.move_to_row(self.rows - 1).await?;
+        self.writer.write_str("\r\n").await?;
+        self.writer.write_str(text).await?;
+        self.writer.restore_cursor().await
+    }
+
+    /// Redraw the input line at the bottom of the screen
+    pub async fn draw_input(&mut self, prompt: &str, input: &str) -> Result<(), W::Error> {
+        if self.writer.ansi_enabled() {
+            self.move_to_row(self.rows).await?;
+        }
+        self.writer.clear_line().await?;
+        self.writer.write_prompt(prompt).await?;
+        self.writer.write_str(input).await
+    }
+
+    /// Reset the scroll region to the full screen
+    pub async fn release(&mut self) -> Result<(), W::Error> {
+        self.writer.reset_scroll_region().await
+    }
+
+    async fn move_to_row(&mut self, row: u16) -> Result<(), W::Error> {
+        self.writer.move_cursor_to(row, 1).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWriter;
+    use embassy_futures::block_on;
+
+    #[test]
+    fn test_messages_scroll_above_input_line() {
+        let mut mock = MockWriter::new();
+        {
+            let mut layout = SplitLayout::new(TerminalWriter::new(&mut mock, true), 24);
+            block_on(layout.init()).unwrap();
+            block_on(layout.draw_input("> ", "hel")).unwrap();
+            block_on(layout.push_message("alice: hi")).unwrap();
+            block_on(layout.push_message("bob: hey")).unwrap();
+        }
+
+        assert_eq!(
+            mock.as_str(),
+            concat!(
+                "\x1b[2J\x1b[H",
+                // Scroll region covers everything but the last row
+                "\x1b[1;23r",
+                "\x1b[24;1H",
+                // Input line drawn on the pinned bottom row
+                "\x1b[24;1H\r\x1b[K> hel",
+                // Each message scrolls the region, then restores the cursor
+                "\x1b7\x1b[23;1H\r\nalice: hi\x1b8",
+                "\x1b7\x1b[23;1H\r\nbob: hey\x1b8",
+            )
+        );
+    }
+
+    #[test]
+    fn test_plain_fallback() {
+        let mut mock = MockWriter::new();
+        {
+            let mut layout = SplitLayout::new(TerminalWriter::new(&mut mock, false), 24);
+            block_on(layout.init()).unwrap();
+            block_on(layout.push_message("alice: hi")).unwrap();
+        }
+        assert_eq!(mock.as_str(), "alice: hi\r\n");
+    }
+}
@@ -1,5 +1,5 @@
 #![no_std]
-#![doc = include_str!("../README.md")]
+#![cfg_attr(not(doctest), doc = include_str!("../README.MD"))]
 
 //! An interactive terminal library for `no_std` embedded systems.
 //!
@@ -10,11 +10,35 @@ pub mod terminal;
 pub mod history;
 pub mod parser;
 pub mod writer;
+pub mod layout;
+pub mod completion;
+pub mod bindings;
+mod undo;
+pub mod registry;
+pub mod alias;
+pub mod highlight;
+pub mod width;
+pub mod buffered;
+pub mod error;
 
-pub use terminal::{Terminal, TerminalConfig};
-pub use history::{History, HistoryConfig};
-pub use parser::{CommandParser, ParsedCommand};
-pub use writer::TerminalWriter;
+#[cfg(test)]
+mod mock;
+
+pub use terminal::{
+    EditMode, EmptyDeletePolicy, KeyMacro, NewlineMode, ReadStep, SpaceHook, Terminal,
+    TerminalConfig, ValidatorFn, WordCheck,
+};
+pub use history::{DedupMode, History, HistoryConfig, HistoryStorage};
+pub use parser::{CommandParser, ParsedCommand, ParsedCommandRef, StreamingParser};
+pub use writer::{Segments, TerminalWriter, Theme, WriteFmtError};
+pub use buffered::BufferedWriter;
+pub use error::CapacityError;
+pub use layout::SplitLayout;
+pub use completion::{CompletionConfig, CompletionLayout};
+pub use bindings::KeyBindings;
+pub use registry::CommandRegistry;
+pub use alias::{AliasTable, Resolution};
+pub use highlight::{Highlight, HighlighterFn};
 
 /// Re-export commonly used types
 pub mod prelude {
@@ -0,0 +1,87 @@
+//! In-memory I/O doubles shared by the unit tests.
+
+use core::convert::Infallible;
+use embedded_io_async::{ErrorType, Read, Write};
+use heapless::Vec;
+
+/// Writer that captures everything written to it
+pub struct MockWriter {
+    pub data: Vec<u8, 1024>,
+    /// Number of `write` calls
+    pub writes: usize,
+}
+
+impl MockWriter {
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            writes: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.data).unwrap()
+    }
+}
+
+impl ErrorType for MockWriter {
+    type Error = Infallible;
+}
+
+impl Write for MockWriter {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.data.extend_from_slice(buf).unwrap();
+        self.writes += 1;
+        Ok(buf.len())
+    }
+}
+
+/// Reader that yields a fixed byte sequence, one byte per read by default
+///
+/// Once the data is used up every read returns `Ok(0)`, like a closed link,
+/// or never completes for a reader made with [`MockReader::stalling`].
+pub struct MockReader<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    stall: bool,
+}
+
+impl<'a> MockReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self::chunked(data, 1)
+    }
+
+    /// Yield up to `chunk` bytes per read
+    pub fn chunked(data: &'a [u8], chunk: usize) -> Self {
+        Self {
+            data,
+            chunk,
+            stall: false,
+        }
+    }
+
+    /// Wait forever once the data is used up, like an idle link
+    pub fn stalling(data: &'a [u8]) -> Self {
+        Self {
+            stall: true,
+            ..Self::new(data)
+        }
+    }
+}
+
+impl ErrorType for MockReader<'_> {
+    type Error = Infallible;
+}
+
+impl Read for MockReader<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if self.stall && self.data.is_empty() {
+            core::future::pending::<()>().await;
+        }
+        let len = self.chunk.min(buf.len()).min(self.data.len());
+        let (head, rest) = self.data.split_at(len);
+        buf[..len].copy_from_slice(head);
+        self.data = rest;
+        Ok(len)
+    }
+}
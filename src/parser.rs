@@ -1,3 +1,6 @@
+use core::fmt;
+use core::str::FromStr;
+
 use heapless::{String, Vec};
 
 /// A parsed command with its arguments
@@ -15,6 +18,14 @@ impl<const MAX_ARGS: usize, const BUF_SIZE: usize> ParsedCommand<MAX_ARGS, BUF_S
         &self.command
     }
 
+    /// Check whether the command name equals `name`, ignoring ASCII case
+    ///
+    /// Only `A`-`Z` and `a`-`z` are folded; other characters must match
+    /// exactly.
+    pub fn name_eq_ignore_case(&self, name: &str) -> bool {
+        self.command.eq_ignore_ascii_case(name)
+    }
+
     /// Get the number of arguments
     pub fn arg_count(&self) -> usize {
         self.args.len()
@@ -25,6 +36,54 @@ impl<const MAX_ARGS: usize, const BUF_SIZE: usize> ParsedCommand<MAX_ARGS, BUF_S
         self.args.get(index).map(|s| s.as_str())
     }
 
+    /// Parse an argument with [`FromStr`]
+    ///
+    /// Returns `None` if the argument is missing or does not parse.
+    pub fn arg_parse<T: FromStr>(&self, index: usize) -> Option<T> {
+        self.arg(index)?.parse().ok()
+    }
+
+    /// Parse an argument as `u32`
+    pub fn arg_u32(&self, index: usize) -> Option<u32> {
+        self.arg_parse(index)
+    }
+
+    /// Parse an argument as `i32`
+    pub fn arg_i32(&self, index: usize) -> Option<i32> {
+        self.arg_parse(index)
+    }
+
+    /// Parse an argument as `f32`
+    pub fn arg_f32(&self, index: usize) -> Option<f32> {
+        self.arg_parse(index)
+    }
+
+    /// Treat the first argument as a command of its own
+    ///
+    /// For `net wifi connect home` this is `wifi` with the arguments
+    /// `connect home`. Returns `None` without arguments.
+    pub fn subcommand(&self) -> Option<ParsedCommandRef<'_, MAX_ARGS>> {
+        let (command, rest) = self.args.split_first()?;
+        Some(ParsedCommandRef {
+            command,
+            args: rest.iter().map(|arg| arg.as_str()).collect(),
+        })
+    }
+
+    /// Match the name and leading arguments against `path`, returning the
+    /// arguments after it
+    ///
+    /// `["net", "wifi"]` matches `net wifi connect home` and returns
+    /// `connect home`.
+    pub fn match_path(&self, path: &[&str]) -> Option<&[String<BUF_SIZE>]> {
+        let (name, rest) = path.split_first()?;
+        if self.command != *name || self.args.len() < rest.len() {
+            return None;
+        }
+        let (head, tail) = self.args.split_at(rest.len());
+        head.iter().zip(rest).all(|(arg, part)| arg == part).then_some(tail)
+    }
+
     /// Get all arguments joined by a separator
     pub fn args_joined(&self, separator: &str) -> Option<String<BUF_SIZE>> {
         if self.args.is_empty() {
@@ -50,6 +109,220 @@ impl<const MAX_ARGS: usize, const BUF_SIZE: usize> ParsedCommand<MAX_ARGS, BUF_S
         }
         Some(result)
     }
+
+    /// Get the flag arguments (`-x`, `--long`, `--name=value`) as flag names
+    ///
+    /// Arguments starting with `-` or `--` are flags, except a lone `-` and
+    /// negative numbers such as `-5`. A `--` argument ends flag processing.
+    /// For `--name=value` only `--name` is yielded.
+    pub fn flags(&self) -> impl Iterator<Item = &str> {
+        self.option_args()
+            .filter(|arg| is_flag(arg))
+            .map(|arg| arg.split('=').next().unwrap_or(arg))
+    }
+
+    /// Check whether a flag such as `--verbose` or `-v` was given
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.flags().any(|f| f == flag)
+    }
+
+    /// Get the value of an option given as `--name value` or `--name=value`
+    pub fn option(&self, name: &str) -> Option<&str> {
+        let mut args = self.option_args();
+        while let Some(arg) = args.next() {
+            if arg == name {
+                return args.next().filter(|value| !is_flag(value));
+            }
+            if let Some(value) = arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Get the arguments that are not flags
+    ///
+    /// Everything after a `--` argument is positional. Because the parser has
+    /// no schema, the value of a `--name value` option is also returned here;
+    /// use the `--name=value` form when mixing options with positionals.
+    pub fn positionals(&self) -> impl Iterator<Item = &str> {
+        let mut after_terminator = false;
+        self.args.iter().map(|s| s.as_str()).filter(move |&arg| {
+            if after_terminator {
+                return true;
+            }
+            if arg == "--" {
+                after_terminator = true;
+                return false;
+            }
+            !is_flag(arg)
+        })
+    }
+
+    /// Get the value of a `key=value` argument
+    ///
+    /// The value is everything after the first `=`, so it may itself contain
+    /// `=`. If the key appears more than once, the first occurrence wins.
+    pub fn kv(&self, key: &str) -> Option<&str> {
+        self.kv_pairs().find(|&(k, _)| k == key).map(|(_, value)| value)
+    }
+
+    /// Iterate over `key=value` arguments as `(key, value)` pairs
+    ///
+    /// Arguments without `=` and flags such as `--mode=fast` are skipped; use
+    /// [`arg`](Self::arg) and [`option`](Self::option) for those.
+    pub fn kv_pairs(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.args
+            .iter()
+            .map(|s| s.as_str())
+            .filter(|arg| !is_flag(arg))
+            .filter_map(|arg| arg.split_once('='))
+            .filter(|(key, _)| !key.is_empty())
+    }
+
+    /// Arguments before a `--` terminator
+    fn option_args(&self) -> impl Iterator<Item = &str> {
+        self.args.iter().map(|s| s.as_str()).take_while(|&arg| arg != "--")
+    }
+}
+
+/// A parsed command borrowing its name and arguments from the input line
+///
+/// Produced by [`CommandParser::parse_ref`].
+#[derive(Debug, Clone)]
+pub struct ParsedCommandRef<'a, const MAX_ARGS: usize> {
+    /// The command name
+    pub command: &'a str,
+    /// Command arguments
+    pub args: Vec<&'a str, MAX_ARGS>,
+}
+
+impl<'a, const MAX_ARGS: usize> ParsedCommandRef<'a, MAX_ARGS> {
+    /// Get the command name
+    pub fn name(&self) -> &'a str {
+        self.command
+    }
+
+    /// Check whether the command name equals `name`, ignoring ASCII case
+    pub fn name_eq_ignore_case(&self, name: &str) -> bool {
+        self.command.eq_ignore_ascii_case(name)
+    }
+
+    /// Get the number of arguments
+    pub fn arg_count(&self) -> usize {
+        self.args.len()
+    }
+
+    /// Get an argument by index
+    pub fn arg(&self, index: usize) -> Option<&'a str> {
+        self.args.get(index).copied()
+    }
+
+    /// Treat the first argument as a command of its own, see
+    /// [`ParsedCommand::subcommand`]
+    pub fn subcommand(&self) -> Option<ParsedCommandRef<'a, MAX_ARGS>> {
+        let (command, rest) = self.args.split_first()?;
+        Some(ParsedCommandRef {
+            command,
+            args: rest.iter().copied().collect(),
+        })
+    }
+
+    /// Match the name and leading arguments against `path`, see
+    /// [`ParsedCommand::match_path`]
+    pub fn match_path(&self, path: &[&str]) -> Option<&[&'a str]> {
+        let (name, rest) = path.split_first()?;
+        if self.command != *name || self.args.len() < rest.len() {
+            return None;
+        }
+        let (head, tail) = self.args.split_at(rest.len());
+        (head == rest).then_some(tail)
+    }
+}
+
+/// Role of a character in a command line, see [`Scanner`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Class {
+    /// Argument text, quoted or not
+    Text,
+    /// A backslash escaping the next character
+    Escape,
+    /// The character after an escaping backslash
+    ///
+    /// `backslash` is set inside double quotes for characters other than `"`
+    /// and `\`, where the backslash is kept literally.
+    Escaped { backslash: bool },
+    /// A quote opening or closing a quoted section
+    Quote,
+    /// An unquoted space between arguments
+    Separator,
+}
+
+/// Quote and escape state of a command line
+///
+/// Every quote-aware function of [`CommandParser`] and [`Tokens`] classifies
+/// characters through this, so they all split a line the same way.
+#[derive(Debug, Clone, Copy, Default)]
+struct Scanner {
+    /// The quote character of the currently open quoted section, if any
+    quote: Option<char>,
+    /// Whether the previous character was an escaping backslash
+    escaped: bool,
+}
+
+impl Scanner {
+    /// Classify the next character and update the state
+    ///
+    /// A backslash escapes the next character except inside single quotes.
+    /// Inside one kind of quote the other kind is plain text.
+    fn next(&mut self, c: char) -> Class {
+        if self.escaped {
+            self.escaped = false;
+            // Inside double quotes only `\"` and `\\` are escapes
+            let backslash = self.quote == Some('"') && c != '"' && c != '\\';
+            return Class::Escaped { backslash };
+        }
+        match c {
+            '\\' if self.quote != Some('\'') => {
+                self.escaped = true;
+                Class::Escape
+            }
+            '"' | '\'' if self.quote.is_none() => {
+                self.quote = Some(c);
+                Class::Quote
+            }
+            c if self.quote == Some(c) => {
+                self.quote = None;
+                Class::Quote
+            }
+            ' ' if self.quote.is_none() => Class::Separator,
+            _ => Class::Text,
+        }
+    }
+}
+
+/// Trim the whitespace around a line, keeping an escaped one at its end
+///
+/// Without this `a\ ` would lose its space and end in a lone backslash.
+fn trim_line(input: &str) -> &str {
+    let line = input.trim_start();
+    let trimmed = line.trim_end();
+    let mut scanner = Scanner::default();
+    for c in trimmed.chars() {
+        scanner.next(c);
+    }
+    match line[trimmed.len()..].chars().next() {
+        Some(c) if scanner.escaped => &line[..trimmed.len() + c.len_utf8()],
+        _ => trimmed,
+    }
+}
+
+/// Check whether an argument looks like a flag (`-x`, `--long`)
+fn is_flag(arg: &str) -> bool {
+    let mut chars = arg.chars();
+    chars.next() == Some('-')
+        && matches!(chars.next(), Some(c) if !c.is_ascii_digit() && c != '.')
+        && arg != "--"
 }
 
 /// Command parser for splitting input into command and arguments
@@ -58,39 +331,63 @@ pub struct CommandParser;
 impl CommandParser {
     /// Parse a command line into command and arguments
     ///
-    /// Supports basic quote handling for arguments with spaces.
+    /// Supports basic quote handling for arguments with spaces. Both double
+    /// and single quotes group text; inside one kind of quote the other is
+    /// kept literally, so `"it's"` and `'say "hi"'` are single arguments.
+    ///
+    /// A backslash escapes the next character: `\"`, `\ ` and `\\` produce the
+    /// literal character without toggling quotes or splitting. Inside double
+    /// quotes only `\"` and `\\` are escapes, and inside single quotes a
+    /// backslash is always literal. A trailing backslash is kept as is.
+    ///
+    /// A quote left open at the end of the line yields
+    /// [`ParseError::UnclosedQuote`].
     pub fn parse<const MAX_ARGS: usize, const BUF_SIZE: usize>(
         input: &str,
     ) -> Result<ParsedCommand<MAX_ARGS, BUF_SIZE>, ParseError> {
-        let trimmed = input.trim();
+        let trimmed = trim_line(input);
         if trimmed.is_empty() {
             return Err(ParseError::EmptyInput);
         }
 
         let mut parts = Vec::<String<BUF_SIZE>, MAX_ARGS>::new();
         let mut current = String::<BUF_SIZE>::new();
-        let mut in_quotes = false;
-        let mut chars = trimmed.chars().peekable();
+        let mut scanner = Scanner::default();
+        // Whether the current token contained quotes, so `""` yields an empty argument
+        let mut quoted = false;
 
-        while let Some(c) = chars.next() {
-            match c {
-                '"' => {
-                    in_quotes = !in_quotes;
+        for c in trimmed.chars() {
+            match scanner.next(c) {
+                Class::Escape => {}
+                Class::Escaped { backslash: true } => {
+                    current.push('\\').map_err(|_| ParseError::ArgTooLong)?;
+                    current.push(c).map_err(|_| ParseError::ArgTooLong)?;
+                }
+                Class::Text | Class::Escaped { .. } => {
+                    current.push(c).map_err(|_| ParseError::ArgTooLong)?;
                 }
-                ' ' if !in_quotes => {
-                    if !current.is_empty() {
+                Class::Quote => quoted = true,
+                Class::Separator => {
+                    if !current.is_empty() || quoted {
                         parts.push(current.clone()).map_err(|_| ParseError::TooManyArgs)?;
                         current.clear();
+                        quoted = false;
                     }
                 }
-                _ => {
-                    current.push(c).map_err(|_| ParseError::ArgTooLong)?;
-                }
             }
         }
 
+        if scanner.quote.is_some() {
+            return Err(ParseError::UnclosedQuote);
+        }
+
+        // A trailing backslash has nothing to escape and is kept literally
+        if scanner.escaped {
+            current.push('\\').map_err(|_| ParseError::ArgTooLong)?;
+        }
+
         // Push final argument
-        if !current.is_empty() {
+        if !current.is_empty() || quoted {
             parts.push(current).map_err(|_| ParseError::TooManyArgs)?;
         }
 
@@ -104,6 +401,69 @@ impl CommandParser {
         Ok(ParsedCommand { command, args })
     }
 
+    /// Parse a command line, ignoring everything from the first unquoted `#`
+    ///
+    /// A `#` inside quotes or escaped with a backslash is kept as part of the
+    /// argument. A line that only holds a comment yields [`ParseError::EmptyInput`].
+    pub fn parse_with_comments<const MAX_ARGS: usize, const BUF_SIZE: usize>(
+        input: &str,
+    ) -> Result<ParsedCommand<MAX_ARGS, BUF_SIZE>, ParseError> {
+        Self::parse(Self::strip_comment(input))
+    }
+
+    /// Cut the input at the first `#` outside quotes, using the same quoting
+    /// rules as [`CommandParser::parse`]
+    fn strip_comment(input: &str) -> &str {
+        let mut scanner = Scanner::default();
+        for (i, c) in input.char_indices() {
+            if scanner.next(c) == Class::Text && c == '#' && scanner.quote.is_none() {
+                return &input[..i];
+            }
+        }
+        input
+    }
+
+    /// Count the arguments in a command line without building any strings
+    ///
+    /// Tokens are split the same way as [`CommandParser::parse`], including
+    /// quote handling. The command name itself is not counted, so the result
+    /// matches [`ParsedCommand::arg_count`] of a successful parse.
+    pub fn count_args(input: &str) -> usize {
+        Self::tokens(input).count().saturating_sub(1)
+    }
+
+    /// Iterate over the tokens of a command line as slices of `input`
+    ///
+    /// Tokens are split the same way as [`CommandParser::parse`], including
+    /// quote handling, but nothing is copied. Since a slice cannot be
+    /// rewritten, only a token that is a single quoted section without
+    /// backslashes, such as `"hello world"`, has its quotes removed; any
+    /// other token is yielded exactly as typed.
+    pub fn tokens(input: &str) -> Tokens<'_> {
+        Tokens { rest: trim_line(input) }
+    }
+
+    /// Get the text after the command and its first `n` arguments exactly as
+    /// typed
+    ///
+    /// Arguments are skipped the same way as [`CommandParser::parse`] splits
+    /// them, so quoted arguments count as one. The remainder keeps its inner
+    /// spacing, quotes and backslashes; only the spaces separating it from
+    /// the skipped arguments and the whitespace around the line are removed.
+    /// This lets a command such as `echo` print `rest_after(line, 0)` without
+    /// collapsing runs of spaces. Returns `None` if the line has fewer than
+    /// `n` arguments.
+    ///
+    /// [`ParsedCommand`] owns copies of its arguments and cannot point back
+    /// into the line, so this takes the original `input` instead.
+    pub fn rest_after(input: &str, n: usize) -> Option<&str> {
+        let mut tokens = Self::tokens(input);
+        for _ in 0..=n {
+            tokens.next()?;
+        }
+        Some(tokens.rest.trim_start_matches(' '))
+    }
+
     /// Simple split on whitespace (faster but no quote support)
     pub fn parse_simple<const MAX_ARGS: usize, const BUF_SIZE: usize>(
         input: &str,
@@ -130,6 +490,25 @@ impl CommandParser {
         Ok(ParsedCommand { command, args })
     }
 
+    /// Split on whitespace without copying, borrowing from `input`
+    ///
+    /// Like [`CommandParser::parse_simple`], quotes and backslashes are not
+    /// interpreted: removing them would need an owned copy. Use
+    /// [`CommandParser::parse`] for quoted arguments.
+    pub fn parse_ref<const MAX_ARGS: usize>(
+        input: &str,
+    ) -> Result<ParsedCommandRef<'_, MAX_ARGS>, ParseError> {
+        let mut parts = input.split_whitespace();
+        let command = parts.next().ok_or(ParseError::EmptyInput)?;
+
+        let mut args = Vec::new();
+        for part in parts {
+            args.push(part).map_err(|_| ParseError::TooManyArgs)?;
+        }
+
+        Ok(ParsedCommandRef { command, args })
+    }
+
     /// Parse with a maximum number of splits (remaining text goes into last arg)
     pub fn parse_max_split<const MAX_ARGS: usize, const BUF_SIZE: usize>(
         input: &str,
@@ -173,6 +552,181 @@ impl CommandParser {
     }
 }
 
+/// Iterator over borrowed tokens, see [`CommandParser::tokens`]
+#[derive(Debug, Clone)]
+pub struct Tokens<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+
+        let mut scanner = Scanner::default();
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| scanner.next(c) == Class::Separator)
+            .map_or(rest.len(), |(i, _)| i);
+
+        let (token, rest) = rest.split_at(end);
+        self.rest = rest;
+        Some(strip_quotes(token))
+    }
+}
+
+/// Parser for command lines that arrive in pieces, e.g. over a packet link
+///
+/// Chunks are collected until a `\n` ends the line, which is then parsed
+/// with [`CommandParser::parse`]; a `\r` before it is ignored and blank
+/// lines are skipped. Text after the last `\n` stays pending for the next
+/// chunk.
+pub struct StreamingParser<const MAX_ARGS: usize, const BUF_SIZE: usize> {
+    line: String<BUF_SIZE>,
+    /// The line being received already failed, so the rest of it is dropped
+    discarding: bool,
+    /// A `\r` was received and is held back until the next character shows
+    /// whether it is part of a `\r\n` line ending
+    carriage_return: bool,
+}
+
+impl<const MAX_ARGS: usize, const BUF_SIZE: usize> StreamingParser<MAX_ARGS, BUF_SIZE> {
+    /// Create a parser with no pending input
+    pub fn new() -> Self {
+        Self {
+            line: String::new(),
+            discarding: false,
+            carriage_return: false,
+        }
+    }
+
+    /// Add a chunk of input, yielding a result for every line it completes
+    ///
+    /// The whole line is collected in a buffer of `BUF_SIZE` bytes, so a
+    /// longer line fails with [`ParseError::LineTooLong`] even if each of its
+    /// arguments would fit. That error, and [`ParseError::TooManyArgs`] for a
+    /// line with too many arguments, is reported as soon as it is known,
+    /// without waiting for the end of the line; the rest of that line is then
+    /// dropped. The iterator must be run to completion, or the
+    /// rest of the chunk is lost.
+    pub fn feed<'p, 'c>(&'p mut self, chunk: &'c str) -> Commands<'p, 'c, MAX_ARGS, BUF_SIZE> {
+        Commands {
+            parser: self,
+            chars: chunk.chars(),
+        }
+    }
+
+    /// Get the text received since the last complete line
+    ///
+    /// A `\r` at the end of the received text is not included until the
+    /// next character arrives.
+    pub fn pending(&self) -> &str {
+        &self.line
+    }
+
+    /// Drop any partially received line
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.discarding = false;
+        self.carriage_return = false;
+    }
+
+    /// Add a character to the current line, failing it early if possible
+    fn push(&mut self, c: char) -> Option<Result<ParsedCommand<MAX_ARGS, BUF_SIZE>, ParseError>> {
+        if self.line.push(c).is_err() {
+            return Some(self.fail(ParseError::LineTooLong));
+        }
+        // Check the count whenever a new argument may have started
+        let before = &self.line[..self.line.len() - c.len_utf8()];
+        if c != ' '
+            && before.ends_with(' ')
+            && CommandParser::tokens(&self.line).count() > MAX_ARGS
+        {
+            return Some(self.fail(ParseError::TooManyArgs));
+        }
+        None
+    }
+
+    /// Drop the rest of the current line and report `error`
+    fn fail(&mut self, error: ParseError) -> Result<ParsedCommand<MAX_ARGS, BUF_SIZE>, ParseError> {
+        self.line.clear();
+        self.discarding = true;
+        Err(error)
+    }
+}
+
+impl<const MAX_ARGS: usize, const BUF_SIZE: usize> Default for StreamingParser<MAX_ARGS, BUF_SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Iterator over the lines completed by a chunk, see [`StreamingParser::feed`]
+pub struct Commands<'p, 'c, const MAX_ARGS: usize, const BUF_SIZE: usize> {
+    parser: &'p mut StreamingParser<MAX_ARGS, BUF_SIZE>,
+    chars: core::str::Chars<'c>,
+}
+
+impl<const MAX_ARGS: usize, const BUF_SIZE: usize> Iterator
+    for Commands<'_, '_, MAX_ARGS, BUF_SIZE>
+{
+    type Item = Result<ParsedCommand<MAX_ARGS, BUF_SIZE>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let parser = &mut *self.parser;
+        for c in self.chars.by_ref() {
+            // A held back `\r` before `\n` is dropped, any other is kept
+            let carriage_return = core::mem::replace(&mut parser.carriage_return, false);
+            if c == '\n' {
+                let line = core::mem::take(&mut parser.line);
+                if core::mem::replace(&mut parser.discarding, false) {
+                    continue;
+                }
+                match CommandParser::parse(&line) {
+                    Err(ParseError::EmptyInput) => continue,
+                    result => return Some(result),
+                }
+            }
+            if parser.discarding {
+                continue;
+            }
+            if carriage_return {
+                if let Some(result) = parser.push('\r') {
+                    return Some(result);
+                }
+            }
+            if c == '\r' {
+                parser.carriage_return = true;
+                continue;
+            }
+            if let Some(result) = parser.push(c) {
+                return Some(result);
+            }
+        }
+        None
+    }
+}
+
+/// Remove the quotes around a token that is one quoted section
+fn strip_quotes(token: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = token
+            .strip_prefix(quote)
+            .and_then(|rest| rest.strip_suffix(quote))
+        {
+            if !inner.contains(quote) && !inner.contains('\\') {
+                return inner;
+            }
+        }
+    }
+    token
+}
+
 /// Errors that can occur during parsing
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ParseError {
@@ -180,8 +734,23 @@ pub enum ParseError {
     TooManyArgs,
     ArgTooLong,
     UnclosedQuote,
+    LineTooLong,
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ParseError::EmptyInput => "empty command",
+            ParseError::TooManyArgs => "too many arguments",
+            ParseError::ArgTooLong => "argument too long",
+            ParseError::UnclosedQuote => "unclosed quote",
+            ParseError::LineTooLong => "line too long",
+        })
+    }
+}
+
+impl core::error::Error for ParseError {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,4 +789,324 @@ mod tests {
         assert_eq!(parsed.arg_count(), 1);
         assert_eq!(parsed.arg(0), Some("this is a long message"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_count_args() {
+        assert_eq!(CommandParser::count_args(""), 0);
+        assert_eq!(CommandParser::count_args("hello"), 0);
+        assert_eq!(CommandParser::count_args("send 192.168.1.1  message "), 2);
+    }
+
+    #[test]
+    fn test_count_args_with_quotes() {
+        assert_eq!(CommandParser::count_args(r#"send peer "hello world""#), 2);
+        assert_eq!(CommandParser::count_args(r#"echo "a b c" d"#), 2);
+    }
+
+    #[test]
+    fn test_parse_keeps_empty_quoted_arg() {
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse(r#"set key """#).unwrap();
+        assert_eq!(parsed.name(), "set");
+        assert_eq!(parsed.arg_count(), 2);
+        assert_eq!(parsed.arg(0), Some("key"));
+        assert_eq!(parsed.arg(1), Some(""));
+        assert_eq!(CommandParser::count_args(r#"set key """#), 2);
+
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse(r#"cmd "" x"#).unwrap();
+        assert_eq!(parsed.arg(0), Some(""));
+        assert_eq!(parsed.arg(1), Some("x"));
+    }
+
+    #[test]
+    fn test_parse_single_quotes() {
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse("echo 'hello world'").unwrap();
+        assert_eq!(parsed.arg_count(), 1);
+        assert_eq!(parsed.arg(0), Some("hello world"));
+    }
+
+    #[test]
+    fn test_parse_mixed_quotes() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse(r#"echo "it's" 'say "hi"'"#).unwrap();
+        assert_eq!(parsed.arg_count(), 2);
+        assert_eq!(parsed.arg(0), Some("it's"));
+        assert_eq!(parsed.arg(1), Some(r#"say "hi""#));
+        assert_eq!(CommandParser::count_args(r#"echo "it's" 'say "hi"'"#), 2);
+    }
+
+    #[test]
+    fn test_parse_quote_mid_token() {
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse("set na'me x'y z").unwrap();
+        assert_eq!(parsed.arg_count(), 2);
+        assert_eq!(parsed.arg(0), Some("name xy"));
+        assert_eq!(parsed.arg(1), Some("z"));
+    }
+
+    #[test]
+    fn test_parse_backslash_escapes() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse(r#"echo \"quoted\" a\ b c\\d"#).unwrap();
+        assert_eq!(parsed.arg_count(), 3);
+        assert_eq!(parsed.arg(0), Some(r#""quoted""#));
+        assert_eq!(parsed.arg(1), Some("a b"));
+        assert_eq!(parsed.arg(2), Some(r"c\d"));
+        assert_eq!(CommandParser::count_args(r#"echo \"quoted\" a\ b c\\d"#), 3);
+    }
+
+    #[test]
+    fn test_parse_backslash_in_quotes() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse(r#"echo "a \"b\" \n" 'c\d'"#).unwrap();
+        assert_eq!(parsed.arg(0), Some(r#"a "b" \n"#));
+        assert_eq!(parsed.arg(1), Some(r"c\d"));
+    }
+
+    #[test]
+    fn test_parse_escaped_and_joined_quotes() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse(r#"say "he said \"hi\"" 'can'\''t'"#).unwrap();
+        assert_eq!(parsed.arg_count(), 2);
+        assert_eq!(parsed.arg(0), Some(r#"he said "hi""#));
+        assert_eq!(parsed.arg(1), Some("can't"));
+    }
+
+    #[test]
+    fn test_parse_unclosed_quote() {
+        for input in [r#"echo "abc"#, "echo 'it", r#"echo "a\""#, r#"echo 'a' "b"#] {
+            let result: Result<ParsedCommand<8, 64>, _> = CommandParser::parse(input);
+            assert_eq!(result.unwrap_err(), ParseError::UnclosedQuote, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_trailing_backslash() {
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse(r"echo abc\").unwrap();
+        assert_eq!(parsed.arg(0), Some(r"abc\"));
+    }
+
+    #[test]
+    fn test_parse_escaped_trailing_space() {
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse(r"echo a\ ").unwrap();
+        assert_eq!(parsed.arg(0), Some("a "));
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse("echo a\\  \r\n").unwrap();
+        assert_eq!(parsed.arg_count(), 1);
+        assert_eq!(parsed.arg(0), Some("a "));
+        assert_eq!(CommandParser::rest_after("echo a\\  ", 0), Some("a\\ "));
+    }
+
+    #[test]
+    fn test_parse_with_comments() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_with_comments("reboot # after firmware update").unwrap();
+        assert_eq!(parsed.name(), "reboot");
+        assert_eq!(parsed.arg_count(), 0);
+
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_with_comments(r#"echo "a # b" \#c #note"#).unwrap();
+        assert_eq!(parsed.arg(0), Some("a # b"));
+        assert_eq!(parsed.arg(1), Some("#c"));
+        assert_eq!(parsed.arg_count(), 2);
+
+        let result: Result<ParsedCommand<8, 64>, _> =
+            CommandParser::parse_with_comments("# just a note");
+        assert_eq!(result.unwrap_err(), ParseError::EmptyInput);
+    }
+
+    #[test]
+    fn test_flags_and_options() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_simple("set --verbose -n 5 --mode=fast file.txt").unwrap();
+        let mut flags = parsed.flags();
+        assert_eq!(flags.next(), Some("--verbose"));
+        assert_eq!(flags.next(), Some("-n"));
+        assert_eq!(flags.next(), Some("--mode"));
+        assert_eq!(flags.next(), None);
+
+        assert!(parsed.is_set("--verbose"));
+        assert!(!parsed.is_set("--quiet"));
+        assert_eq!(parsed.option("-n"), Some("5"));
+        assert_eq!(parsed.option("--mode"), Some("fast"));
+        assert_eq!(parsed.option("--verbose"), None);
+        assert_eq!(parsed.option("--missing"), None);
+    }
+
+    #[test]
+    fn test_kv_pairs() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_simple("config set baud=115200 parity=none --mode=x =1").unwrap();
+        let mut pairs = parsed.kv_pairs();
+        assert_eq!(pairs.next(), Some(("baud", "115200")));
+        assert_eq!(pairs.next(), Some(("parity", "none")));
+        assert_eq!(pairs.next(), None);
+
+        assert_eq!(parsed.arg(0), Some("set"));
+        assert_eq!(parsed.kv("parity"), Some("none"));
+        assert_eq!(parsed.kv("mode"), None);
+        assert_eq!(parsed.kv("stop"), None);
+    }
+
+    #[test]
+    fn test_kv_value_containing_equals() {
+        let parsed: ParsedCommand<4, 64> =
+            CommandParser::parse_simple("env PATH=a=b empty=").unwrap();
+        assert_eq!(parsed.kv("PATH"), Some("a=b"));
+        assert_eq!(parsed.kv("empty"), Some(""));
+    }
+
+    #[test]
+    fn test_positionals() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_simple("mv -f --mode=x a -5 -- -b").unwrap();
+        let mut positionals = parsed.positionals();
+        assert_eq!(positionals.next(), Some("a"));
+        assert_eq!(positionals.next(), Some("-5"));
+        assert_eq!(positionals.next(), Some("-b"));
+        assert_eq!(positionals.next(), None);
+        // Flags after the terminator are not flags
+        assert!(!parsed.is_set("-b"));
+    }
+
+    #[test]
+    fn test_tokens_match_parse() {
+        let input = r#"  send peer "hello world" 'a "b"' "" x  "#;
+        let parsed: ParsedCommand<8, 64> = CommandParser::parse(input).unwrap();
+        let mut tokens = CommandParser::tokens(input);
+        assert_eq!(tokens.next(), Some(parsed.name()));
+        for arg in &parsed.args {
+            assert_eq!(tokens.next(), Some(arg.as_str()));
+        }
+        assert_eq!(tokens.next(), None);
+    }
+
+    #[test]
+    fn test_tokens_keep_escapes_as_typed() {
+        let tokens: Vec<&str, 4> = CommandParser::tokens(r#"echo a\ b "x\"y" pre"fix""#).collect();
+        assert_eq!(tokens.as_slice(), &["echo", r"a\ b", r#""x\"y""#, r#"pre"fix""#]);
+        assert_eq!(CommandParser::tokens("   ").next(), None);
+    }
+
+    #[test]
+    fn test_rest_after() {
+        let line = r#"  echo  "a  b"   keep   these  "spaces"  "#;
+        assert_eq!(CommandParser::rest_after(line, 0), Some(r#""a  b"   keep   these  "spaces""#));
+        assert_eq!(CommandParser::rest_after(line, 1), Some(r#"keep   these  "spaces""#));
+        assert_eq!(CommandParser::rest_after(line, 4), Some(""));
+        assert_eq!(CommandParser::rest_after(line, 5), None);
+        assert_eq!(CommandParser::rest_after("echo", 0), Some(""));
+        assert_eq!(CommandParser::rest_after("   ", 0), None);
+    }
+
+    #[test]
+    fn test_parse_ref() {
+        let line = "  send  192.168.1.1 \"hi there\" ";
+        let parsed: ParsedCommandRef<4> = CommandParser::parse_ref(line).unwrap();
+        assert_eq!(parsed.name(), "send");
+        assert_eq!(parsed.arg_count(), 3);
+        assert_eq!(parsed.arg(0), Some("192.168.1.1"));
+        // Quotes are kept as typed
+        assert_eq!(parsed.arg(1), Some("\"hi"));
+        assert_eq!(parsed.arg(3), None);
+    }
+
+    #[test]
+    fn test_parse_ref_errors() {
+        assert_eq!(CommandParser::parse_ref::<4>(" \t ").unwrap_err(), ParseError::EmptyInput);
+        assert_eq!(CommandParser::parse_ref::<1>("a b c").unwrap_err(), ParseError::TooManyArgs);
+    }
+
+    #[test]
+    fn test_name_eq_ignore_case() {
+        let parsed: ParsedCommand<4, 64> = CommandParser::parse_simple("Help me").unwrap();
+        assert!(parsed.name_eq_ignore_case("help"));
+        assert!(parsed.name_eq_ignore_case("HELP"));
+        assert!(!parsed.name_eq_ignore_case("hel"));
+
+        let parsed: ParsedCommandRef<4> = CommandParser::parse_ref("HELP").unwrap();
+        assert!(parsed.name_eq_ignore_case("help"));
+    }
+
+    #[test]
+    fn test_arg_parse() {
+        let parsed: ParsedCommand<8, 64> =
+            CommandParser::parse_simple("set pin 13 -2 0.5 300").unwrap();
+        assert_eq!(parsed.arg_parse::<u8>(1), Some(13));
+        assert_eq!(parsed.arg_parse::<u8>(0), None);
+        assert_eq!(parsed.arg_parse::<u8>(4), None);
+        assert_eq!(parsed.arg_i32(2), Some(-2));
+        assert_eq!(parsed.arg_u32(2), None);
+        assert_eq!(parsed.arg_f32(3), Some(0.5));
+        assert_eq!(parsed.arg_u32(4), Some(300));
+        assert_eq!(parsed.arg_u32(5), None);
+    }
+
+    #[test]
+    fn test_streaming_parser_joins_chunks() {
+        let mut parser = StreamingParser::<4, 32>::new();
+        assert!(parser.feed("send pe").next().is_none());
+        assert_eq!(parser.pending(), "send pe");
+
+        let mut commands = parser.feed("er \"hi there\"\r\n\nls\nre");
+        let first = commands.next().unwrap().unwrap();
+        assert_eq!(first.name(), "send");
+        assert_eq!(first.arg(1), Some("hi there"));
+        assert_eq!(commands.next().unwrap().unwrap().name(), "ls");
+        assert!(commands.next().is_none());
+        assert_eq!(parser.pending(), "re");
+
+        let mut commands = parser.feed("boot\n");
+        assert_eq!(commands.next().unwrap().unwrap().name(), "reboot");
+        assert!(commands.next().is_none());
+    }
+
+    #[test]
+    fn test_streaming_parser_fails_early() {
+        let mut parser = StreamingParser::<2, 8>::new();
+        let mut commands = parser.feed("a b c");
+        assert_eq!(commands.next().unwrap().unwrap_err(), ParseError::TooManyArgs);
+        assert!(commands.next().is_none());
+        // The rest of the failed line is dropped
+        let mut commands = parser.feed(" d\nok\n");
+        assert_eq!(commands.next().unwrap().unwrap().name(), "ok");
+
+        let mut commands = parser.feed("a b\nabcd efgh\nhi\n");
+        assert_eq!(commands.next().unwrap().unwrap().name(), "a");
+        // Each argument fits on its own, but the line needs 9 bytes
+        assert_eq!(commands.next().unwrap().unwrap_err(), ParseError::LineTooLong);
+        assert_eq!(commands.next().unwrap().unwrap().name(), "hi");
+        assert!(commands.next().is_none());
+
+        // The `\r` of a CRLF ending doesn't count towards the line length,
+        // even when the `\n` comes in the next chunk
+        assert!(parser.feed("abcd efg\r").next().is_none());
+        let parsed = parser.feed("\n").next().unwrap().unwrap();
+        assert_eq!(parsed.arg(0), Some("efg"));
+    }
+
+    #[test]
+    fn test_subcommands() {
+        let parsed: ParsedCommand<8, 32> = CommandParser::parse("net wifi connect home").unwrap();
+        let wifi = parsed.subcommand().unwrap();
+        assert_eq!(wifi.name(), "wifi");
+        assert_eq!(wifi.args.as_slice(), &["connect", "home"]);
+        let connect = wifi.subcommand().unwrap();
+        assert_eq!(connect.name(), "connect");
+        assert_eq!(connect.arg(0), Some("home"));
+        assert!(connect.subcommand().unwrap().subcommand().is_none());
+
+        let args = parsed.match_path(&["net", "wifi"]).unwrap();
+        assert_eq!(args.len(), 2);
+        assert_eq!(args[0], "connect");
+        assert!(parsed.match_path(&["net", "eth"]).is_none());
+        assert!(parsed.match_path(&["wifi"]).is_none());
+        assert!(parsed.match_path(&["net", "wifi", "connect", "home", "x"]).is_none());
+        assert_eq!(wifi.match_path(&["wifi", "connect"]), Some(&["home"][..]));
+    }
+
+    #[test]
+    fn test_parse_error_display() {
+        use core::fmt::Write;
+        let mut message = String::<32>::new();
+        write!(message, "{}", ParseError::TooManyArgs).unwrap();
+        assert_eq!(message, "too many arguments");
+    }
+}
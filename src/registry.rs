@@ -0,0 +1,168 @@
+use heapless::Vec;
+
+use crate::alias::{AliasTable, Resolution};
+use crate::error::CapacityError;
+use crate::parser::ParsedCommand;
+
+/// Function invoked for a registered command
+pub type CommandHandler<R, const MAX_ARGS: usize, const BUF_SIZE: usize> =
+    fn(&ParsedCommand<MAX_ARGS, BUF_SIZE>) -> R;
+
+/// Fixed-capacity table of named commands
+///
+/// Holds up to `N` `(name, handler)` pairs and dispatches parsed commands to
+/// the handler registered under their name, so callers don't have to match on
+/// [`ParsedCommand::name`] by hand.
+pub struct CommandRegistry<R, const N: usize, const MAX_ARGS: usize, const BUF_SIZE: usize> {
+    commands: Vec<(&'static str, CommandHandler<R, MAX_ARGS, BUF_SIZE>), N>,
+}
+
+impl<R, const N: usize, const MAX_ARGS: usize, const BUF_SIZE: usize>
+    CommandRegistry<R, N, MAX_ARGS, BUF_SIZE>
+{
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self { commands: Vec::new() }
+    }
+
+    /// Register a handler for a command name
+    ///
+    /// Registering a name again replaces its handler. Fails if the registry
+    /// is full.
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        handler: CommandHandler<R, MAX_ARGS, BUF_SIZE>,
+    ) -> Result<(), CapacityError> {
+        if let Some(entry) = self.commands.iter_mut().find(|(n, _)| *n == name) {
+            entry.1 = handler;
+            return Ok(());
+        }
+        self.commands.push((name, handler)).map_err(|_| CapacityError)
+    }
+
+    /// Check whether a command is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.commands.iter().any(|(n, _)| *n == name)
+    }
+
+    /// Iterate over the registered command names in registration order
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.iter().map(|(name, _)| *name)
+    }
+
+    /// Get the number of registered commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check if no commands are registered
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Run the handler registered for the command's name
+    ///
+    /// Returns `None` if no command with that name is registered.
+    pub fn dispatch(&self, command: &ParsedCommand<MAX_ARGS, BUF_SIZE>) -> Option<R> {
+        self.commands
+            .iter()
+            .find(|(name, _)| *name == command.name())
+            .map(|(_, handler)| handler(command))
+    }
+
+    /// Run the handler for the command's name after resolving aliases and
+    /// abbreviations, see [`AliasTable::resolve`]
+    ///
+    /// Fails with [`Resolution::Unknown`] or [`Resolution::Ambiguous`] if
+    /// the name does not lead to a registered command.
+    pub fn dispatch_aliased<const A: usize>(
+        &self,
+        command: &ParsedCommand<MAX_ARGS, BUF_SIZE>,
+        aliases: &AliasTable<A>,
+    ) -> Result<R, Resolution> {
+        let name = match aliases.resolve(command.name(), self.names()) {
+            Resolution::Found(name) => name,
+            unresolved => return Err(unresolved),
+        };
+        self.commands
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, handler)| handler(command))
+            .ok_or(Resolution::Unknown)
+    }
+}
+
+impl<R, const N: usize, const MAX_ARGS: usize, const BUF_SIZE: usize> Default
+    for CommandRegistry<R, N, MAX_ARGS, BUF_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::CommandParser;
+
+    type Cmd = ParsedCommand<4, 32>;
+
+    fn echo(cmd: &Cmd) -> usize {
+        cmd.arg_count()
+    }
+
+    fn reboot(_: &Cmd) -> usize {
+        99
+    }
+
+    #[test]
+    fn test_dispatch() {
+        let mut registry = CommandRegistry::<usize, 4, 4, 32>::new();
+        registry.register("echo", echo).unwrap();
+        registry.register("reboot", reboot).unwrap();
+
+        let cmd: Cmd = CommandParser::parse("echo a b").unwrap();
+        assert_eq!(registry.dispatch(&cmd), Some(2));
+        let cmd: Cmd = CommandParser::parse("reboot").unwrap();
+        assert_eq!(registry.dispatch(&cmd), Some(99));
+        let cmd: Cmd = CommandParser::parse("halt").unwrap();
+        assert_eq!(registry.dispatch(&cmd), None);
+    }
+
+    #[test]
+    fn test_dispatch_aliased() {
+        let mut registry = CommandRegistry::<usize, 4, 4, 32>::new();
+        registry.register("echo", echo).unwrap();
+        registry.register("reboot", reboot).unwrap();
+        registry.register("reset", reboot).unwrap();
+        let mut aliases = AliasTable::<2>::new();
+        aliases.add("say", "echo").unwrap();
+
+        let cmd: Cmd = CommandParser::parse("say a b").unwrap();
+        assert_eq!(registry.dispatch_aliased(&cmd, &aliases), Ok(2));
+        let cmd: Cmd = CommandParser::parse("reb").unwrap();
+        assert_eq!(registry.dispatch_aliased(&cmd, &aliases), Ok(99));
+        let cmd: Cmd = CommandParser::parse("re").unwrap();
+        assert_eq!(registry.dispatch_aliased(&cmd, &aliases), Err(Resolution::Ambiguous));
+        let cmd: Cmd = CommandParser::parse("halt").unwrap();
+        assert_eq!(registry.dispatch_aliased(&cmd, &aliases), Err(Resolution::Unknown));
+    }
+
+    #[test]
+    fn test_register_capacity_and_replace() {
+        let mut registry = CommandRegistry::<usize, 1, 4, 32>::new();
+        assert!(registry.is_empty());
+        registry.register("echo", echo).unwrap();
+        assert_eq!(registry.register("reboot", reboot), Err(CapacityError));
+
+        // Re-registering an existing name does not need a free slot
+        registry.register("echo", reboot).unwrap();
+        let cmd: Cmd = CommandParser::parse("echo").unwrap();
+        assert_eq!(registry.dispatch(&cmd), Some(99));
+        assert!(registry.contains("echo"));
+        assert!(!registry.contains("reboot"));
+        assert_eq!(registry.names().next(), Some("echo"));
+        assert_eq!(registry.len(), 1);
+    }
+}
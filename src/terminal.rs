@@ -1,11 +1,27 @@
-use embassy_futures::select::{select, Either};
-use embassy_sync::{blocking_mutex::raw::RawMutex, signal::Signal};
+use core::future::{self, Future};
+use core::ops::{Range, RangeInclusive};
+use core::str::FromStr;
+
+use embassy_futures::select::{select, select4, Either, Either4};
+use embassy_sync::blocking_mutex::raw::{NoopRawMutex, RawMutex};
+use embassy_sync::signal::Signal;
 use embedded_io_async::{Read, Write as AsyncWrite};
 use heapless::{String, Vec};
 
+use crate::bindings::KeyBindings;
+use crate::completion::{self, Candidates, CompletionConfig};
+use crate::error::CapacityError;
+use crate::highlight::{self, HighlighterFn};
 use crate::history::History;
-use crate::parser::{CommandParser, ParsedCommand};
-use crate::writer::TerminalWriter;
+use crate::undo::{Snapshot, UndoStack};
+use crate::width::display_width;
+use crate::writer::{TerminalWriter, WriteFmtError};
+
+/// Maximum number of keys captured by [`Terminal::start_recording`]
+pub const MAX_MACRO_KEYS: usize = 32;
+
+/// Keys captured while recording, for [`Terminal::replay`]
+pub type KeyMacro = Vec<KeyCode, MAX_MACRO_KEYS>;
 
 /// Configuration for the terminal
 #[derive(Clone, Copy)]
@@ -14,10 +30,64 @@ pub struct TerminalConfig {
     pub buffer_size: usize,
     /// Prompt string to display
     pub prompt: &'static str,
+    /// Prompt shown for lines continued with a trailing `\`
+    pub continuation_prompt: &'static str,
     /// Enable echo of typed characters
     pub echo: bool,
     /// Enable ANSI escape codes for better terminal control
     pub ansi_enabled: bool,
+    /// What Backspace/Delete do when the line is empty
+    pub empty_delete: EmptyDeletePolicy,
+    /// Hook run on the word before the cursor whenever a space is typed
+    pub space_hook: Option<SpaceHook>,
+    /// Ring the bell when typing into a full buffer
+    pub bell_on_full: bool,
+    /// Terminal width in columns, if known
+    ///
+    /// Used to lay out completion lists and, with ANSI support, to redraw
+    /// input that wraps over several rows. When unknown, the input is
+    /// assumed to fit on one row.
+    pub width: Option<u16>,
+    /// Terminal height in rows, if known
+    pub height: Option<u16>,
+    /// Columns before the prompt holding other output, e.g. a label printed
+    /// without a newline
+    ///
+    /// Redraws only clear the line from this offset on, so that output is
+    /// preserved. Requires ANSI support.
+    pub line_offset: u16,
+    /// Tab completion settings
+    pub completion: CompletionConfig,
+    /// Keys produced by control bytes
+    pub key_bindings: KeyBindings,
+    /// Callback coloring the input line when the reader redraws it
+    pub highlighter: Option<HighlighterFn>,
+    /// Emacs-style or modal Vi-style key handling
+    pub edit_mode: EditMode,
+    /// Which bytes end a line
+    pub newline: NewlineMode,
+    /// Check run on a line when Enter is pressed
+    ///
+    /// A rejected line is kept for further editing and the message is shown
+    /// below it.
+    pub validator: Option<ValidatorFn>,
+    /// Signal end of file on Ctrl+D even when the line is not empty
+    ///
+    /// By default Ctrl+D only ends input on an empty line and deletes the
+    /// character under the cursor otherwise, like in a shell.
+    pub ctrl_d_always_eof: bool,
+    /// Number of spaces, up to 8, that Tab inserts when no completer is set
+    ///
+    /// With 0 such a Tab is ignored, so a literal tab never enters the
+    /// buffer and the line always displays as parsed.
+    pub tab_width: u8,
+    /// Maximum length of a command in bytes
+    ///
+    /// Editing treats the buffer as full once the line reaches this length,
+    /// so terminals sharing one `BUF_SIZE` can enforce different limits. The
+    /// buffer size still applies on top, and the default of `usize::MAX`
+    /// leaves `BUF_SIZE` as the only limit.
+    pub max_command_len: usize,
 }
 
 impl Default for TerminalConfig {
@@ -25,12 +95,135 @@ impl Default for TerminalConfig {
         Self {
             buffer_size: 128,
             prompt: "> ",
+            continuation_prompt: "> ",
             echo: true,
             ansi_enabled: true,
+            empty_delete: EmptyDeletePolicy::Silent,
+            space_hook: None,
+            bell_on_full: true,
+            width: None,
+            height: None,
+            line_offset: 0,
+            completion: CompletionConfig::default(),
+            key_bindings: KeyBindings::default(),
+            highlighter: None,
+            edit_mode: EditMode::Emacs,
+            newline: NewlineMode::CrLf,
+            validator: None,
+            ctrl_d_always_eof: false,
+            tab_width: 0,
+            max_command_len: usize::MAX,
+        }
+    }
+}
+
+impl TerminalConfig {
+    /// Write the configuration as a human-readable list, one field per line
+    pub async fn write_config<W: AsyncWrite>(
+        &self,
+        writer: &mut TerminalWriter<'_, W>,
+    ) -> Result<(), WriteFmtError<W::Error>> {
+        writer.write_fmt(format_args!("buffer_size:  {}\r\n", self.buffer_size)).await?;
+        writer.write_fmt(format_args!("prompt:       {:?}\r\n", self.prompt)).await?;
+        writer.write_fmt(format_args!("continuation: {:?}\r\n", self.continuation_prompt)).await?;
+        writer.write_fmt(format_args!("echo:         {}\r\n", self.echo)).await?;
+        writer.write_fmt(format_args!("ansi_enabled: {}\r\n", self.ansi_enabled)).await?;
+        writer.write_fmt(format_args!("empty_delete: {:?}\r\n", self.empty_delete)).await?;
+        let space_hook = if self.space_hook.is_some() { "set" } else { "none" };
+        writer.write_fmt(format_args!("space_hook:   {}\r\n", space_hook)).await?;
+        writer.write_fmt(format_args!("bell_on_full: {}\r\n", self.bell_on_full)).await?;
+        match self.width {
+            Some(width) => writer.write_fmt(format_args!("width:        {}\r\n", width)).await?,
+            None => writer.write_str("width:        unknown\r\n").await.map_err(WriteFmtError::Io)?,
+        }
+        match self.height {
+            Some(height) => writer.write_fmt(format_args!("height:       {}\r\n", height)).await?,
+            None => writer.write_str("height:       unknown\r\n").await.map_err(WriteFmtError::Io)?,
+        }
+        writer.write_fmt(format_args!("line_offset:  {}\r\n", self.line_offset)).await?;
+        let completer = if self.completion.completer.is_some() { "set" } else { "none" };
+        writer.write_fmt(format_args!("completer:    {}\r\n", completer)).await?;
+        writer
+            .write_fmt(format_args!("completion:   {:?}\r\n", self.completion.layout))
+            .await?;
+        writer
+            .write_fmt(format_args!("ignore_case:  {}\r\n", self.completion.ignore_case))
+            .await?;
+        let default_keys = self.key_bindings == KeyBindings::default();
+        let key_bindings = if default_keys { "default" } else { "custom" };
+        writer.write_fmt(format_args!("key_bindings: {}\r\n", key_bindings)).await?;
+        let highlighter = if self.highlighter.is_some() { "set" } else { "none" };
+        writer.write_fmt(format_args!("highlighter:  {}\r\n", highlighter)).await?;
+        writer.write_fmt(format_args!("edit_mode:    {:?}\r\n", self.edit_mode)).await?;
+        writer.write_fmt(format_args!("newline:      {:?}\r\n", self.newline)).await?;
+        let validator = if self.validator.is_some() { "set" } else { "none" };
+        writer.write_fmt(format_args!("validator:    {}\r\n", validator)).await?;
+        writer.write_fmt(format_args!("ctrl_d_always_eof: {}\r\n", self.ctrl_d_always_eof)).await?;
+        writer.write_fmt(format_args!("tab_width:    {}\r\n", self.tab_width)).await?;
+        match self.max_command_len {
+            usize::MAX => {
+                writer.write_str("max_command_len: none\r\n").await.map_err(WriteFmtError::Io)
+            }
+            len => writer.write_fmt(format_args!("max_command_len: {}\r\n", len)).await,
         }
     }
 }
 
+/// Behavior of Backspace/Delete when the line buffer is empty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmptyDeletePolicy {
+    /// Ignore the key press
+    Silent,
+    /// Ring the terminal bell
+    Bell,
+    /// Treat the key press like Ctrl+D and signal end of file
+    EndOfFile,
+}
+
+/// Bytes that end a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NewlineMode {
+    /// Only `\r` is Enter, `\n` is ignored
+    Cr,
+    /// Only `\n` is Enter, `\r` is ignored
+    Lf,
+    /// Either byte is Enter, but a `\n` right after `\r` is ignored, so a
+    /// `\r\n` pair submits a single line
+    CrLf,
+}
+
+/// Key handling style of the line editor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditMode {
+    /// Every key edits directly, with Ctrl shortcuts
+    Emacs,
+    /// Modal editing: Escape enters normal mode, where `h`/`l` move, `0`/`$`
+    /// jump to the line ends, `x` deletes, `dd`/`dw` delete the line or up to
+    /// the next word, and `i`/`a` return to inserting
+    ///
+    /// A lone Escape cannot be told apart from the start of an escape
+    /// sequence until the next byte arrives, so normal mode begins with the
+    /// key pressed after Escape. Each new line starts in insert mode.
+    Vi,
+}
+
+/// Outcome of a [`SpaceHook`] check on the word completed by a space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordCheck {
+    /// Keep the word and insert the space
+    Accept,
+    /// Replace the word with the given text, then insert the space
+    Replace(&'static str),
+    /// Refuse the space and ring the bell
+    Reject,
+}
+
+/// Callback validating or transforming a word when the user types a space
+pub type SpaceHook = fn(&str) -> WordCheck;
+
+/// Check run on an entered line, returning the message to show on rejection
+pub type ValidatorFn = fn(&str) -> Result<(), &'static str>;
+
 /// Key codes for special keys
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum KeyCode {
@@ -43,17 +236,88 @@ pub enum KeyCode {
     ArrowDown,
     ArrowLeft,
     ArrowRight,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    /// Toggles overwrite mode
+    Insert,
     CtrlC,
     CtrlD,
+    /// Swap the characters around the cursor (Ctrl+T)
+    Transpose,
+    /// Cut the word before the cursor (Ctrl+W)
+    KillWord,
+    /// Cut from the start of the line to the cursor (Ctrl+U)
+    KillToStart,
+    /// Cut from the cursor to the end of the line (Ctrl+K)
+    KillToEnd,
+    /// Paste the last cut text at the cursor (Ctrl+Y)
+    Yank,
+    /// Revert the last edit (Ctrl+_)
+    Undo,
+    /// Reapply an undone edit (Ctrl+^)
+    Redo,
+    /// Application-defined action bound in [`KeyBindings`]
+    Custom(u8),
+    /// Ctrl plus a letter, carried as `a`..=`z`, for control bytes without a
+    /// dedicated key in the default [`KeyBindings`]
+    Ctrl(u8),
+    /// A printable byte right after `ESC`, sent by Alt/Meta combinations
+    ///
+    /// Alt+B and Alt+F move by words; other combinations are left to the
+    /// application. Not produced in [`EditMode::Vi`], where `ESC` enters
+    /// normal mode.
+    Alt(u8),
     Char(u8),
+    /// A complete non-ASCII character
+    Unicode(char),
 }
 
 /// Main terminal structure
-pub struct Terminal<const BUF_SIZE: usize> {
+///
+/// `UNDO_DEPTH` is the number of edits that can be undone.
+pub struct Terminal<const BUF_SIZE: usize, const UNDO_DEPTH: usize = 4> {
     config: TerminalConfig,
     buffer: Vec<u8, BUF_SIZE>,
     cursor_pos: usize,
     escape_state: EscapeState,
+    /// Bytes of a multibyte UTF-8 character that has not fully arrived yet
+    utf8_pending: Vec<u8, 4>,
+    /// Candidates found by the last ambiguous completion
+    completions: Candidates,
+    /// Cached state for cycling through `completions` with repeated Tabs
+    completion_cycle: Option<CompletionCycle>,
+    /// Typed characters replace the one under the cursor instead of
+    /// being inserted
+    overwrite: bool,
+    /// Earlier buffer states for undo and redo
+    undo: UndoStack<BUF_SIZE, UNDO_DEPTH>,
+    /// The last key typed a character, so further typing extends that edit
+    typing: bool,
+    /// Text removed by the last kill command
+    kill_buffer: String<BUF_SIZE>,
+    /// In Vi normal mode, keys are commands instead of text
+    vi_normal: bool,
+    /// First key of a two-key Vi command such as `dd`
+    vi_pending: Option<u8>,
+    /// The previous byte was `\r`, for [`NewlineMode::CrLf`]
+    after_cr: bool,
+    /// Keys handled since recording started
+    recording: Option<KeyMacro>,
+    /// Every byte is taken literally, see [`Terminal::set_raw_mode`]
+    raw_mode: bool,
+    /// Byte ending a line in raw mode
+    raw_terminator: u8,
+}
+
+/// Position within a cached candidate list while cycling with Tab
+#[derive(Debug, Clone, Copy)]
+struct CompletionCycle {
+    /// Byte offset where the completed word starts
+    start: usize,
+    /// Index of the candidate to insert on the next Tab
+    next: usize,
 }
 
 /// State machine for parsing ANSI escape sequences
@@ -62,9 +326,13 @@ enum EscapeState {
     Normal,
     Escape,
     Bracket,
+    /// Accumulating the numeric parameter of an `ESC[<n>~` sequence
+    Param(u8),
+    /// After `ESC O` (SS3), used by some terminals for Home/End
+    Ss3,
 }
 
-impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
+impl<const BUF_SIZE: usize, const UNDO_DEPTH: usize> Terminal<BUF_SIZE, UNDO_DEPTH> {
     /// Create a new terminal instance
     pub fn new(config: TerminalConfig) -> Self {
         Self {
@@ -72,6 +340,19 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
             buffer: Vec::new(),
             cursor_pos: 0,
             escape_state: EscapeState::Normal,
+            utf8_pending: Vec::new(),
+            completions: Vec::new(),
+            completion_cycle: None,
+            overwrite: false,
+            undo: UndoStack::new(),
+            typing: false,
+            kill_buffer: String::new(),
+            vi_normal: false,
+            vi_pending: None,
+            after_cr: false,
+            recording: None,
+            raw_mode: false,
+            raw_terminator: b'\r',
         }
     }
 
@@ -84,40 +365,149 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
     pub fn clear_buffer(&mut self) {
         self.buffer.clear();
         self.cursor_pos = 0;
+        self.completion_cycle = None;
+        self.vi_normal = false;
+        self.vi_pending = None;
+    }
+
+    /// Check whether keys are currently read as Vi normal-mode commands
+    pub fn is_vi_normal(&self) -> bool {
+        self.vi_normal
     }
 
-    /// Get the current cursor position
+    /// Get the current cursor position, in characters from the start of the line
     pub fn cursor_position(&self) -> usize {
+        self.buffer[..self.cursor_pos]
+            .iter()
+            .filter(|&&b| !is_utf8_continuation(b))
+            .count()
+    }
+
+    /// Get the length of the buffer in characters
+    pub fn char_count(&self) -> usize {
+        self.buffer.iter().filter(|&&b| !is_utf8_continuation(b)).count()
+    }
+
+    /// Get the number of bytes that can still be inserted
+    ///
+    /// Counts up to [`TerminalConfig::max_command_len`] when that is below
+    /// `BUF_SIZE`.
+    pub fn remaining_capacity(&self) -> usize {
+        self.max_len().saturating_sub(self.buffer.len())
+    }
+
+    /// Check whether the buffer has no room for another byte
+    pub fn is_full(&self) -> bool {
+        self.buffer.len() >= self.max_len()
+    }
+
+    /// Get the longest line editing may produce
+    fn max_len(&self) -> usize {
+        self.config.max_command_len.min(BUF_SIZE)
+    }
+
+    /// Get the number of screen columns before the cursor
+    ///
+    /// Unlike [`Terminal::cursor_position`] this counts wide characters as two
+    /// columns and combining marks as none, see [`display_width`].
+    pub fn cursor_column(&self) -> usize {
+        core::str::from_utf8(&self.buffer[..self.cursor_pos]).map_or(0, display_width)
+    }
+
+    /// Get the number of screen columns the buffer occupies
+    pub fn display_width(&self) -> usize {
+        self.buffer_str().map_or(0, display_width)
+    }
+
+    /// Get the byte offset of the cursor within the buffer
+    ///
+    /// This differs from [`Terminal::cursor_position`] when the line contains
+    /// multibyte UTF-8 characters.
+    pub fn cursor_byte_offset(&self) -> usize {
         self.cursor_pos
     }
 
+    /// Byte offset of the character boundary before the cursor
+    fn prev_char_boundary(&self) -> usize {
+        let mut pos = self.cursor_pos.saturating_sub(1);
+        while pos > 0 && is_utf8_continuation(self.buffer[pos]) {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// Byte offset of the character boundary after the cursor
+    fn next_char_boundary(&self) -> usize {
+        let mut pos = (self.cursor_pos + 1).min(self.buffer.len());
+        while pos < self.buffer.len() && is_utf8_continuation(self.buffer[pos]) {
+            pos += 1;
+        }
+        pos
+    }
+
     /// Process a single byte of input, handling ANSI escape sequences
+    ///
+    /// Multibyte UTF-8 characters are collected until complete and reported
+    /// as a single [`KeyCode::Unicode`]. An incomplete sequence interrupted by
+    /// any other byte is discarded, so it can never reach the line buffer.
     pub fn process_byte(&mut self, byte: u8) -> Option<KeyCode> {
+        if self.raw_mode {
+            if byte == self.raw_terminator {
+                return Some(KeyCode::Enter);
+            }
+            return Some(KeyCode::Char(byte));
+        }
+        let after_cr = core::mem::replace(&mut self.after_cr, byte == b'\r');
+        if !self.utf8_pending.is_empty() {
+            if is_utf8_continuation(byte) {
+                return self.continue_utf8(byte);
+            }
+            self.utf8_pending.clear();
+        }
+
         match self.escape_state {
             EscapeState::Normal => {
                 match byte {
-                    b'\r' | b'\n' => Some(KeyCode::Enter),
-                    0x08 | 0x7F => Some(KeyCode::Backspace),
-                    0x03 => Some(KeyCode::CtrlC),
-                    0x04 => Some(KeyCode::CtrlD),
-                    0x09 => Some(KeyCode::Tab),
                     0x1B => {
                         self.escape_state = EscapeState::Escape;
                         None
                     }
-                    byte if byte >= 0x20 && byte < 0x7F => Some(KeyCode::Char(byte)),
-                    _ => None,
+                    byte if (0x20..0x7F).contains(&byte) => Some(KeyCode::Char(byte)),
+                    0xC2..=0xF4 => {
+                        let _ = self.utf8_pending.push(byte);
+                        None
+                    }
+                    b'\r' | b'\n' if self.ignores_newline_byte(byte, after_cr) => None,
+                    // Control bytes, unbound ones fall through as nothing
+                    _ => self.config.key_bindings.get(byte),
                 }
             }
-            EscapeState::Escape => {
-                if byte == b'[' {
+            EscapeState::Escape => match byte {
+                b'[' => {
                     self.escape_state = EscapeState::Bracket;
                     None
-                } else {
+                }
+                b'O' => {
+                    self.escape_state = EscapeState::Ss3;
+                    None
+                }
+                _ if self.config.edit_mode == EditMode::Vi => {
+                    // Not a sequence, so the Escape was a key of its own. It
+                    // is handled right away, through handle_key so macros
+                    // record it, as this byte is the first normal-mode command
+                    self.escape_state = EscapeState::Normal;
+                    self.handle_key(KeyCode::Escape);
+                    self.process_byte(byte)
+                }
+                0x20..=0x7E => {
+                    self.escape_state = EscapeState::Normal;
+                    Some(KeyCode::Alt(byte))
+                }
+                _ => {
                     self.escape_state = EscapeState::Normal;
                     Some(KeyCode::Escape)
                 }
-            }
+            },
             EscapeState::Bracket => {
                 self.escape_state = EscapeState::Normal;
                 match byte {
@@ -125,15 +515,216 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
                     b'B' => Some(KeyCode::ArrowDown),
                     b'C' => Some(KeyCode::ArrowRight),
                     b'D' => Some(KeyCode::ArrowLeft),
-                    b'3' => Some(KeyCode::Delete), // Delete sends ESC[3~
+                    b'H' => Some(KeyCode::Home),
+                    b'F' => Some(KeyCode::End),
+                    b'0'..=b'9' => {
+                        self.escape_state = EscapeState::Param(byte - b'0');
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            EscapeState::Param(param) => match byte {
+                b'0'..=b'9' => {
+                    self.escape_state =
+                        EscapeState::Param(param.saturating_mul(10).saturating_add(byte - b'0'));
+                    None
+                }
+                b'~' => {
+                    self.escape_state = EscapeState::Normal;
+                    match param {
+                        1 | 7 => Some(KeyCode::Home),
+                        2 => Some(KeyCode::Insert),
+                        3 => Some(KeyCode::Delete),
+                        4 | 8 => Some(KeyCode::End),
+                        5 => Some(KeyCode::PageUp),
+                        6 => Some(KeyCode::PageDown),
+                        _ => None,
+                    }
+                }
+                _ => {
+                    // Unknown or unsupported sequence, drop it
+                    self.escape_state = EscapeState::Normal;
+                    None
+                }
+            },
+            EscapeState::Ss3 => {
+                self.escape_state = EscapeState::Normal;
+                match byte {
+                    b'H' => Some(KeyCode::Home),
+                    b'F' => Some(KeyCode::End),
                     _ => None,
                 }
             }
         }
     }
 
+    /// Check whether a line-ending byte is dropped by [`TerminalConfig::newline`]
+    fn ignores_newline_byte(&self, byte: u8, after_cr: bool) -> bool {
+        match self.config.newline {
+            NewlineMode::Cr => byte == b'\n',
+            NewlineMode::Lf => byte == b'\r',
+            NewlineMode::CrLf => byte == b'\n' && after_cr,
+        }
+    }
+
+    /// Add a continuation byte to the pending UTF-8 character
+    fn continue_utf8(&mut self, byte: u8) -> Option<KeyCode> {
+        let _ = self.utf8_pending.push(byte);
+        let expected = match self.utf8_pending[0] {
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            _ => 4,
+        };
+        if self.utf8_pending.len() < expected {
+            return None;
+        }
+        let key = core::str::from_utf8(&self.utf8_pending)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map(KeyCode::Unicode);
+        self.utf8_pending.clear();
+        key
+    }
+
     /// Handle a key press
+    ///
+    /// Every edit except typing further characters into the word being typed
+    /// saves the previous buffer state for [`Terminal::undo`].
     pub fn handle_key(&mut self, key: KeyCode) -> TerminalEvent {
+        if let Some(ref mut keys) = self.recording {
+            let _ = keys.push(key);
+        }
+        if self.raw_mode {
+            return self.apply_raw_key(key);
+        }
+        if matches!(key, KeyCode::Undo | KeyCode::Redo) {
+            self.typing = false;
+            return self.apply_key(key);
+        }
+        let typed = matches!(key, KeyCode::Char(_) | KeyCode::Unicode(_)) && !self.vi_normal;
+        let before = self.snapshot();
+        let event = self.apply_key(key);
+        if self.buffer != before.buffer && !(typed && self.typing) {
+            self.undo.record(before);
+        }
+        self.typing = typed;
+        event
+    }
+
+    /// Stop interpreting input, or go back to line editing
+    ///
+    /// In raw mode [`Terminal::process_byte`] reports every byte as
+    /// [`KeyCode::Char`], skipping escape sequences, UTF-8 decoding and key
+    /// bindings, and [`Terminal::handle_key`] appends it to the buffer as is.
+    /// Only the terminator set with [`Terminal::set_raw_terminator`], `\r`
+    /// by default, becomes Enter and ends the line. Turn off
+    /// [`TerminalConfig::echo`] unless the bytes are printable; a line that
+    /// is not valid UTF-8 fails with [`ReadLineError::Utf8Error`].
+    pub fn set_raw_mode(&mut self, enabled: bool) {
+        self.raw_mode = enabled;
+        self.escape_state = EscapeState::Normal;
+        self.utf8_pending.clear();
+    }
+
+    /// Check whether raw mode is enabled
+    pub fn is_raw_mode(&self) -> bool {
+        self.raw_mode
+    }
+
+    /// Set the byte that ends a line in raw mode
+    pub fn set_raw_terminator(&mut self, byte: u8) {
+        self.raw_terminator = byte;
+    }
+
+    /// Handle a key in raw mode, where only Enter and bytes matter
+    fn apply_raw_key(&mut self, key: KeyCode) -> TerminalEvent {
+        match key {
+            KeyCode::Enter if self.buffer.is_empty() => TerminalEvent::EmptyCommand,
+            KeyCode::Enter => TerminalEvent::CommandReady,
+            KeyCode::Char(_) if self.is_full() => TerminalEvent::BufferFull,
+            KeyCode::Char(byte) => match self.buffer.insert(self.cursor_pos, byte) {
+                Ok(()) => {
+                    self.cursor_pos += 1;
+                    TerminalEvent::BufferChanged
+                }
+                Err(_) => TerminalEvent::BufferFull,
+            },
+            _ => TerminalEvent::None,
+        }
+    }
+
+    /// Start capturing every key passed to [`Terminal::handle_key`]
+    ///
+    /// A recording already in progress is discarded. Keys past
+    /// [`MAX_MACRO_KEYS`] are not captured.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    /// Stop recording and get the captured keys, if recording was started
+    pub fn stop_recording(&mut self) -> Option<KeyMacro> {
+        self.recording.take()
+    }
+
+    /// Check whether keys are being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Handle `keys` in order as if they were typed
+    ///
+    /// Returns the event of the last key, so a key that ends the line, such
+    /// as Enter, should come last.
+    pub fn replay(&mut self, keys: &[KeyCode]) -> TerminalEvent {
+        let mut event = TerminalEvent::None;
+        for &key in keys {
+            event = self.handle_key(key);
+        }
+        event
+    }
+
+    /// Restore the buffer as it was before the last edit
+    pub fn undo(&mut self) -> TerminalEvent {
+        match self.undo.undo(self.snapshot()) {
+            Some(snapshot) => self.restore(snapshot),
+            None => TerminalEvent::None,
+        }
+    }
+
+    /// Reapply an edit reverted by [`Terminal::undo`]
+    pub fn redo(&mut self) -> TerminalEvent {
+        match self.undo.redo(self.snapshot()) {
+            Some(snapshot) => self.restore(snapshot),
+            None => TerminalEvent::None,
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot<BUF_SIZE> {
+        Snapshot {
+            buffer: self.buffer.clone(),
+            cursor: self.cursor_pos,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot<BUF_SIZE>) -> TerminalEvent {
+        self.buffer = snapshot.buffer;
+        self.cursor_pos = snapshot.cursor;
+        self.completion_cycle = None;
+        TerminalEvent::BufferChanged
+    }
+
+    fn apply_key(&mut self, key: KeyCode) -> TerminalEvent {
+        // Any other key edits or moves away from the completed word
+        if key != KeyCode::Tab {
+            self.completion_cycle = None;
+        }
+        if self.vi_normal {
+            if let Some(event) = self.vi_command(key) {
+                return event;
+            }
+        }
+
         match key {
             KeyCode::Enter => {
                 if self.buffer.is_empty() {
@@ -142,18 +733,35 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
                     TerminalEvent::CommandReady
                 }
             }
+            KeyCode::Escape if self.config.edit_mode == EditMode::Vi => {
+                self.vi_normal = true;
+                self.vi_pending = None;
+                TerminalEvent::None
+            }
+            KeyCode::Backspace | KeyCode::Delete if self.buffer.is_empty() => {
+                match self.config.empty_delete {
+                    EmptyDeletePolicy::Silent => TerminalEvent::None,
+                    EmptyDeletePolicy::Bell => TerminalEvent::Bell,
+                    EmptyDeletePolicy::EndOfFile => TerminalEvent::EndOfFile,
+                }
+            }
             KeyCode::Backspace => {
                 if self.cursor_pos > 0 && !self.buffer.is_empty() {
-                    self.buffer.remove(self.cursor_pos - 1);
-                    self.cursor_pos -= 1;
+                    // Remove the whole character so the buffer stays valid UTF-8
+                    let start = self.prev_char_boundary();
+                    let _ = self.replace_range(start..self.cursor_pos, "");
                     TerminalEvent::BufferChanged
                 } else {
                     TerminalEvent::None
                 }
             }
-            KeyCode::Delete => {
+            KeyCode::CtrlD if self.buffer.is_empty() || self.config.ctrl_d_always_eof => {
+                TerminalEvent::EndOfFile
+            }
+            KeyCode::Delete | KeyCode::CtrlD => {
                 if self.cursor_pos < self.buffer.len() {
-                    self.buffer.remove(self.cursor_pos);
+                    let end = self.next_char_boundary();
+                    let _ = self.replace_range(self.cursor_pos..end, "");
                     TerminalEvent::BufferChanged
                 } else {
                     TerminalEvent::None
@@ -161,7 +769,7 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
             }
             KeyCode::ArrowLeft => {
                 if self.cursor_pos > 0 {
-                    self.cursor_pos -= 1;
+                    self.cursor_pos = self.prev_char_boundary();
                     TerminalEvent::CursorMoved
                 } else {
                     TerminalEvent::None
@@ -169,48 +777,454 @@ impl<const BUF_SIZE: usize> Terminal<BUF_SIZE> {
             }
             KeyCode::ArrowRight => {
                 if self.cursor_pos < self.buffer.len() {
-                    self.cursor_pos += 1;
+                    self.cursor_pos = self.next_char_boundary();
+                    TerminalEvent::CursorMoved
+                } else {
+                    TerminalEvent::None
+                }
+            }
+            KeyCode::Home => {
+                if self.cursor_pos > 0 {
+                    self.cursor_pos = 0;
                     TerminalEvent::CursorMoved
                 } else {
                     TerminalEvent::None
                 }
             }
+            KeyCode::End => {
+                if self.cursor_pos < self.buffer.len() {
+                    self.cursor_pos = self.buffer.len();
+                    TerminalEvent::CursorMoved
+                } else {
+                    TerminalEvent::None
+                }
+            }
+            KeyCode::PageUp => TerminalEvent::PageUp,
+            KeyCode::PageDown => TerminalEvent::PageDown,
             KeyCode::ArrowUp => TerminalEvent::HistoryPrevious,
             KeyCode::ArrowDown => TerminalEvent::HistoryNext,
             KeyCode::CtrlC => TerminalEvent::Interrupt,
-            KeyCode::CtrlD => TerminalEvent::EndOfFile,
-            KeyCode::Char(byte) => {
-                if self.buffer.len() < BUF_SIZE {
-                    // Insert at cursor position
-                    if self.cursor_pos == self.buffer.len() {
-                        let _ = self.buffer.push(byte);
-                    } else {
-                        let _ = self.buffer.insert(self.cursor_pos, byte);
-                    }
-                    self.cursor_pos += 1;
-                    TerminalEvent::BufferChanged
-                } else {
-                    TerminalEvent::BufferFull
+            KeyCode::Tab if self.config.completion.completer.is_some() => self.complete(),
+            KeyCode::Tab if self.config.tab_width > 0 => {
+                const SPACES: &str = "        ";
+                let width = (self.config.tab_width as usize).min(SPACES.len());
+                match self.replace_range(self.cursor_pos..self.cursor_pos, &SPACES[..width]) {
+                    Ok(()) => TerminalEvent::BufferChanged,
+                    Err(()) => TerminalEvent::BufferFull,
+                }
+            }
+            KeyCode::Char(b' ') if self.config.space_hook.is_some() => {
+                match self.run_space_hook() {
+                    Ok(()) => self.insert_byte(b' '),
+                    Err(event) => event,
                 }
             }
+            KeyCode::Insert => {
+                self.overwrite = !self.overwrite;
+                TerminalEvent::None
+            }
+            KeyCode::Transpose => self.transpose(),
+            KeyCode::KillWord => self.kill(self.prev_word_start()..self.cursor_pos),
+            KeyCode::Alt(b'b') => self.move_cursor_to(self.prev_word_start()),
+            KeyCode::Alt(b'f') => self.move_cursor_to(self.next_word_end()),
+            KeyCode::KillToStart => self.kill(0..self.cursor_pos),
+            KeyCode::KillToEnd => self.kill(self.cursor_pos..self.buffer.len()),
+            KeyCode::Yank => self.yank(),
+            KeyCode::Undo => self.undo(),
+            KeyCode::Redo => self.redo(),
+            KeyCode::Custom(action) => TerminalEvent::Custom(action),
+            KeyCode::Ctrl(letter) => TerminalEvent::Ctrl(letter),
+            // Only ASCII can be inserted byte-wise without breaking UTF-8
+            KeyCode::Char(byte) if byte.is_ascii() => self.insert_byte(byte),
+            KeyCode::Unicode(c) => self.insert_char(c),
             _ => TerminalEvent::None,
         }
     }
 
+    /// Run a key as a Vi normal-mode command
+    ///
+    /// Returns `None` for keys that behave as in insert mode, such as Enter
+    /// and the arrow keys.
+    fn vi_command(&mut self, key: KeyCode) -> Option<TerminalEvent> {
+        let pending = self.vi_pending.take();
+        let event = match key {
+            KeyCode::Char(b'd') if pending.is_none() => {
+                self.vi_pending = Some(b'd');
+                TerminalEvent::None
+            }
+            KeyCode::Char(b'd') => self.kill(0..self.buffer.len()),
+            KeyCode::Char(b'w') if pending.is_some() => {
+                self.kill(self.cursor_pos..self.next_word_start())
+            }
+            // Anything else cancels a pending `d`
+            KeyCode::Char(_) | KeyCode::Unicode(_) if pending.is_some() => TerminalEvent::None,
+            KeyCode::Char(b'h') | KeyCode::Backspace => self.apply_key(KeyCode::ArrowLeft),
+            KeyCode::Char(b'l') => self.apply_key(KeyCode::ArrowRight),
+            KeyCode::Char(b'0') => self.apply_key(KeyCode::Home),
+            KeyCode::Char(b'$') => self.apply_key(KeyCode::End),
+            KeyCode::Char(b'x') => self.kill(self.cursor_pos..self.next_char_boundary()),
+            KeyCode::Char(b'i') => {
+                self.vi_normal = false;
+                TerminalEvent::None
+            }
+            KeyCode::Char(b'a') => {
+                self.vi_normal = false;
+                self.apply_key(KeyCode::ArrowRight)
+            }
+            KeyCode::Char(_) | KeyCode::Unicode(_) => TerminalEvent::None,
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    /// Byte offset where the word before the cursor starts, skipping spaces
+    /// right before the cursor
+    fn prev_word_start(&self) -> usize {
+        let mut pos = self.cursor_pos;
+        while pos > 0 && self.buffer[pos - 1] == b' ' {
+            pos -= 1;
+        }
+        while pos > 0 && self.buffer[pos - 1] != b' ' {
+            pos -= 1;
+        }
+        pos
+    }
+
+    /// Byte offset where the word after the cursor ends, skipping spaces
+    /// right after the cursor
+    fn next_word_end(&self) -> usize {
+        let mut pos = self.cursor_pos;
+        while pos < self.buffer.len() && self.buffer[pos] == b' ' {
+            pos += 1;
+        }
+        while pos < self.buffer.len() && self.buffer[pos] != b' ' {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Move the cursor to a byte offset, reporting whether it moved
+    fn move_cursor_to(&mut self, pos: usize) -> TerminalEvent {
+        if pos == self.cursor_pos {
+            return TerminalEvent::None;
+        }
+        self.cursor_pos = pos;
+        TerminalEvent::CursorMoved
+    }
+
+    /// Byte offset where the next space-separated word after the cursor starts
+    fn next_word_start(&self) -> usize {
+        let mut pos = self.cursor_pos;
+        while pos < self.buffer.len() && self.buffer[pos] != b' ' {
+            pos += 1;
+        }
+        while pos < self.buffer.len() && self.buffer[pos] == b' ' {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// Insert a byte at the cursor position
+    fn insert_byte(&mut self, byte: u8) -> TerminalEvent {
+        if self.overwrite && self.cursor_pos < self.buffer.len() {
+            return self.overwrite_char(byte as char);
+        }
+        if !self.is_full() {
+            if self.cursor_pos == self.buffer.len() {
+                let _ = self.buffer.push(byte);
+            } else {
+                let _ = self.buffer.insert(self.cursor_pos, byte);
+            }
+            self.cursor_pos += 1;
+            TerminalEvent::BufferChanged
+        } else {
+            TerminalEvent::BufferFull
+        }
+    }
+
+    /// Insert a whole character at the cursor position
+    fn insert_char(&mut self, c: char) -> TerminalEvent {
+        if self.overwrite && self.cursor_pos < self.buffer.len() {
+            return self.overwrite_char(c);
+        }
+        let mut encoded = [0u8; 4];
+        let encoded = c.encode_utf8(&mut encoded);
+        if self.buffer.len() + encoded.len() > self.max_len() {
+            return TerminalEvent::BufferFull;
+        }
+        for (i, &b) in encoded.as_bytes().iter().enumerate() {
+            let _ = self.buffer.insert(self.cursor_pos + i, b);
+        }
+        self.cursor_pos += encoded.len();
+        TerminalEvent::BufferChanged
+    }
+
+    /// Remove a range of the buffer, keeping it for [`KeyCode::Yank`]
+    fn kill(&mut self, range: Range<usize>) -> TerminalEvent {
+        if range.is_empty() {
+            return TerminalEvent::None;
+        }
+        self.kill_buffer.clear();
+        if let Ok(text) = core::str::from_utf8(&self.buffer[range.clone()]) {
+            let _ = self.kill_buffer.push_str(text);
+        }
+        let _ = self.replace_range(range, "");
+        TerminalEvent::BufferChanged
+    }
+
+    /// Insert the last killed text at the cursor
+    fn yank(&mut self) -> TerminalEvent {
+        if self.kill_buffer.is_empty() {
+            return TerminalEvent::None;
+        }
+        let text = self.kill_buffer.clone();
+        match self.replace_range(self.cursor_pos..self.cursor_pos, &text) {
+            Ok(()) => TerminalEvent::BufferChanged,
+            Err(()) => TerminalEvent::BufferFull,
+        }
+    }
+
+    /// Swap the character before the cursor with the one under it
+    ///
+    /// At the end of the line the last two characters are swapped instead.
+    /// The cursor moves past both characters.
+    fn transpose(&mut self) -> TerminalEvent {
+        if self.cursor_pos == 0 || self.char_count() < 2 {
+            return TerminalEvent::None;
+        }
+        if self.cursor_pos == self.buffer.len() {
+            self.cursor_pos = self.prev_char_boundary();
+        }
+        let start = self.prev_char_boundary();
+        let end = self.next_char_boundary();
+        self.buffer[start..end].rotate_left(self.cursor_pos - start);
+        self.cursor_pos = end;
+        TerminalEvent::BufferChanged
+    }
+
+    /// Replace the character under the cursor
+    fn overwrite_char(&mut self, c: char) -> TerminalEvent {
+        let mut encoded = [0u8; 4];
+        let end = self.next_char_boundary();
+        match self.replace_range(self.cursor_pos..end, c.encode_utf8(&mut encoded)) {
+            Ok(()) => TerminalEvent::BufferChanged,
+            Err(()) => TerminalEvent::BufferFull,
+        }
+    }
+
+    /// Switch between inserting and overwriting typed characters
+    ///
+    /// In overwrite mode a typed character replaces the one under the cursor;
+    /// the line only grows when typing at its end. Backspace and Delete work
+    /// the same in both modes. The Insert key toggles the mode.
+    pub fn set_overwrite(&mut self, overwrite: bool) {
+        self.overwrite = overwrite;
+    }
+
+    /// Check whether overwrite mode is active
+    pub fn is_overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    /// Complete the word before the cursor using the configured completer
+    ///
+    /// A single match replaces the word and appends a space; if the two don't
+    /// fit, the line is left unchanged and [`TerminalEvent::BufferFull`] is
+    /// returned. Several matches
+    /// extend the word to their common prefix, are stored for
+    /// [`Terminal::completions`] and reported as
+    /// [`TerminalEvent::CompletionList`]; further Tabs without other edits
+    /// then cycle through them, reusing the cached list instead of calling
+    /// the completer again.
+    pub fn complete(&mut self) -> TerminalEvent {
+        if let Some(cycle) = self.completion_cycle {
+            let candidate = self.completions[cycle.next];
+            if self.replace_range(cycle.start..self.cursor_pos, candidate).is_err() {
+                return TerminalEvent::BufferFull;
+            }
+            self.completion_cycle = Some(CompletionCycle {
+                start: cycle.start,
+                next: (cycle.next + 1) % self.completions.len(),
+            });
+            return TerminalEvent::BufferChanged;
+        }
+
+        self.completions.clear();
+        let Some(completer) = self.config.completion.completer else {
+            return TerminalEvent::None;
+        };
+
+        let start = self.word_at_cursor().start;
+        let (context, word) = match (
+            core::str::from_utf8(&self.buffer[..start]),
+            core::str::from_utf8(&self.buffer[start..self.cursor_pos]),
+        ) {
+            (Ok(context), Ok(word)) => (context, word),
+            _ => return TerminalEvent::None,
+        };
+
+        let mut candidates = Candidates::new();
+        completer(context, &mut candidates);
+        for candidate in candidates {
+            if self.config.completion.matches(candidate, word) {
+                let _ = self.completions.push(candidate);
+            }
+        }
+
+        match self.completions.len() {
+            0 => TerminalEvent::Bell,
+            1 => {
+                let candidate = self.completions[0];
+                self.completions.clear();
+                // Without room for the separator the next argument can't be typed
+                let needs_space = self.buffer.get(self.cursor_pos) != Some(&b' ');
+                let len = self.buffer.len() - (self.cursor_pos - start)
+                    + candidate.len()
+                    + needs_space as usize;
+                if len > self.max_len()
+                    || self.replace_range(start..self.cursor_pos, candidate).is_err()
+                {
+                    return TerminalEvent::BufferFull;
+                }
+                if needs_space {
+                    let _ = self.replace_range(self.cursor_pos..self.cursor_pos, " ");
+                }
+                TerminalEvent::BufferChanged
+            }
+            _ => {
+                // Extend the word as far as all candidates agree
+                let prefix = completion::common_prefix(&self.completions);
+                if prefix.len() > self.cursor_pos - start
+                    && self.replace_range(start..self.cursor_pos, prefix).is_err()
+                {
+                    return TerminalEvent::BufferFull;
+                }
+                self.completion_cycle = Some(CompletionCycle { start, next: 0 });
+                TerminalEvent::CompletionList
+            }
+        }
+    }
+
+    /// Update the terminal size after the window was resized
+    ///
+    /// Returns [`TerminalEvent::BufferChanged`] so the line gets redrawn for
+    /// the new width.
+    pub fn handle_resize(&mut self, columns: u16, rows: u16) -> TerminalEvent {
+        self.config.width = Some(columns);
+        self.config.height = Some(rows);
+        TerminalEvent::BufferChanged
+    }
+
+    /// Get the terminal width in columns, if known
+    pub fn width(&self) -> Option<u16> {
+        self.config.width
+    }
+
+    /// Get the terminal height in rows, if known
+    pub fn height(&self) -> Option<u16> {
+        self.config.height
+    }
+
+    /// Get the candidates of the last ambiguous completion
+    pub fn completions(&self) -> &[&'static str] {
+        &self.completions
+    }
+
+    /// Run the configured space hook on the word before the cursor
+    fn run_space_hook(&mut self) -> Result<(), TerminalEvent> {
+        let Some(hook) = self.config.space_hook else {
+            return Ok(());
+        };
+        let start = self.word_at_cursor().start;
+        let word = core::str::from_utf8(&self.buffer[start..self.cursor_pos]).unwrap_or("");
+        if word.is_empty() {
+            return Ok(());
+        }
+        match hook(word) {
+            WordCheck::Accept => Ok(()),
+            WordCheck::Reject => Err(TerminalEvent::Bell),
+            WordCheck::Replace(replacement) => {
+                // Leave the word alone unless the space after it fits as well
+                let overwrites = self.overwrite && self.cursor_pos < self.buffer.len();
+                let len = self.buffer.len() - (self.cursor_pos - start)
+                    + replacement.len()
+                    + usize::from(!overwrites);
+                if len > self.max_len() {
+                    return Err(TerminalEvent::BufferFull);
+                }
+                self.replace_range(start..self.cursor_pos, replacement)
+                    .map_err(|_| TerminalEvent::BufferFull)
+            }
+        }
+    }
+
     /// Get the current command buffer and clear it
+    #[allow(clippy::result_unit_err)]
     pub fn take_command(&mut self) -> Result<String<BUF_SIZE>, ()> {
         let result = String::from_utf8(self.buffer.clone()).map_err(|_| ())?;
         self.clear_buffer();
+        // Edits of a submitted line can't be undone on the next one
+        self.undo.clear();
         Ok(result)
     }
 
     /// Set the buffer content (useful for history navigation)
+    #[allow(clippy::result_unit_err)]
     pub fn set_buffer(&mut self, content: &str) -> Result<(), ()> {
+        self.completion_cycle = None;
         self.buffer.clear();
         self.buffer.extend_from_slice(content.as_bytes()).map_err(|_| ())?;
         self.cursor_pos = self.buffer.len();
         Ok(())
     }
+
+    /// Get the byte range of the word under (or immediately before) the cursor
+    ///
+    /// Words are delimited by spaces. If the cursor sits between two spaces
+    /// the returned range is empty and starts at the cursor.
+    pub fn word_at_cursor(&self) -> Range<usize> {
+        let start = self.buffer[..self.cursor_pos]
+            .iter()
+            .rposition(|&b| b == b' ')
+            .map_or(0, |i| i + 1);
+        let end = self.buffer[self.cursor_pos..]
+            .iter()
+            .position(|&b| b == b' ')
+            .map_or(self.buffer.len(), |i| self.cursor_pos + i);
+        start..end
+    }
+
+    /// Replace the word at the cursor with `replacement`
+    ///
+    /// The cursor is placed right after the inserted text. If the result
+    /// would not fit in the buffer, the buffer is left unchanged.
+    pub fn replace_current_word(&mut self, replacement: &str) -> Result<(), CapacityError> {
+        self.completion_cycle = None;
+        self.replace_range(self.word_at_cursor(), replacement).map_err(|()| CapacityError)
+    }
+
+    /// Replace a byte range of the buffer and put the cursor after the new text
+    fn replace_range(&mut self, range: Range<usize>, replacement: &str) -> Result<(), ()> {
+        // A line already over the length limit may still shrink
+        let len = self.buffer.len() - range.len() + replacement.len();
+        if len > self.max_len() && len > self.buffer.len() {
+            return Err(());
+        }
+
+        let mut new_buffer = Vec::<u8, BUF_SIZE>::new();
+        new_buffer.extend_from_slice(&self.buffer[..range.start])?;
+        new_buffer.extend_from_slice(replacement.as_bytes())?;
+        new_buffer.extend_from_slice(&self.buffer[range.end..])?;
+
+        self.buffer = new_buffer;
+        self.cursor_pos = range.start + replacement.len();
+        Ok(())
+    }
+}
+
+/// Check whether a byte is a UTF-8 continuation byte (`10xxxxxx`)
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0xC0 == 0x80
 }
 
 /// Events that can occur during terminal operation
@@ -226,22 +1240,125 @@ pub enum TerminalEvent {
     EndOfFile,
     HistoryPrevious,
     HistoryNext,
+    PageUp,
+    PageDown,
+    Bell,
+    /// Several completion candidates match, see [`Terminal::completions`]
+    CompletionList,
+    /// A key bound to [`KeyCode::Custom`] was pressed
+    Custom(u8),
+    /// A [`KeyCode::Ctrl`] shortcut was pressed
+    Ctrl(u8),
+}
+
+/// Screen update requested by [`TerminalReader::feed`]
+///
+/// "Redraw" means: clear the line after [`TerminalConfig::line_offset`],
+/// write the prompt and the buffer, then move the cursor to column
+/// `line_offset + prompt width + cursor_column() + 1` if it is not at the
+/// end.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReadStep<const BUF_SIZE: usize> {
+    /// Nothing to draw
+    None,
+    /// Redraw the line
+    Redraw,
+    /// Move the cursor to the editing position without redrawing
+    MoveCursor,
+    /// An empty line was entered; write `\r\n` and a fresh prompt
+    NewLine,
+    /// Ctrl+C cleared the line; write `^C\r\n` and a fresh prompt
+    Interrupted,
+    /// The line ended in `\`; write `\r\n` and
+    /// [`TerminalConfig::continuation_prompt`], which is also used for
+    /// redraws until the command is submitted
+    Continuation,
+    /// Write `\r\n`, list [`Terminal::completions`], then redraw
+    ListCompletions,
+    /// Ring the terminal bell
+    Bell,
+    /// [`TerminalConfig::validator`] rejected the line, which stays in the
+    /// buffer; write `\r\n`, the message and `\r\n`, then redraw
+    Rejected(&'static str),
+    /// A key bound to [`KeyCode::Custom`] was pressed; `read_line` ignores it
+    Custom(u8),
+    /// A [`KeyCode::Ctrl`] shortcut was pressed; `read_line` ignores it
+    Ctrl(u8),
+    /// A command was entered and added to the history
+    ///
+    /// Write `\r\n` followed by [`TerminalReader::take_queued_output`].
+    Submitted(String<BUF_SIZE>),
 }
 
 /// Terminal reader task that handles async I/O
 pub struct TerminalReader<const BUF_SIZE: usize> {
     terminal: Terminal<BUF_SIZE>,
     history: Option<History<BUF_SIZE>>,
+    /// Output queued to be written right after the next command is submitted
+    deferred: String<BUF_SIZE>,
+    /// Earlier lines of a command continued with a trailing `\`
+    continued: Option<String<BUF_SIZE>>,
+    /// Line being edited when history navigation started
+    search: Option<HistorySearch<BUF_SIZE>>,
+    /// Last batch read from the input; bytes after a submitted line are kept
+    /// for the next read
+    input: [u8; READ_CHUNK],
+    input_pos: usize,
+    input_len: usize,
+    /// Screen row of the cursor, counted from the row the prompt starts on
+    cursor_row: usize,
+    /// Last screen row occupied by the prompt and buffer
+    last_row: usize,
+    /// Ctrl+C ends the read instead of starting a new line
+    interruptible: bool,
+}
+
+/// In-progress line saved while walking through history
+struct HistorySearch<const BUF_SIZE: usize> {
+    line: String<BUF_SIZE>,
+    /// Length of the text before the cursor, which recalled entries must
+    /// start with
+    prefix_len: usize,
 }
 
+/// Maximum number of bytes requested from the reader at once
+const READ_CHUNK: usize = 32;
+
 impl<const BUF_SIZE: usize> TerminalReader<BUF_SIZE> {
     pub fn new(config: TerminalConfig, history: Option<History<BUF_SIZE>>) -> Self {
         Self {
             terminal: Terminal::new(config),
             history,
+            deferred: String::new(),
+            continued: None,
+            search: None,
+            input: [0; READ_CHUNK],
+            input_pos: 0,
+            input_len: 0,
+            cursor_row: 0,
+            last_row: 0,
+            interruptible: false,
         }
     }
 
+    /// Queue output to be written after the next submitted command's newline
+    ///
+    /// Repeated calls append to the queued text. Fails without queuing
+    /// anything if the text does not fit in the remaining space.
+    pub fn queue_output(&mut self, text: &str) -> Result<(), CapacityError> {
+        self.deferred.push_str(text).map_err(|()| CapacityError)
+    }
+
+    /// Get the command history, if one was provided
+    pub fn history(&self) -> Option<&History<BUF_SIZE>> {
+        self.history.as_ref()
+    }
+
+    /// Get mutable access to the command history, if one was provided
+    pub fn history_mut(&mut self) -> Option<&mut History<BUF_SIZE>> {
+        self.history.as_mut()
+    }
+
     /// Read a complete line from the input
     pub async fn read_line<R, W, M>(
         &mut self,
@@ -254,122 +1371,2638 @@ impl<const BUF_SIZE: usize> TerminalReader<BUF_SIZE> {
         W: AsyncWrite,
         M: RawMutex,
     {
-        // Display initial prompt
-        writer.write_prompt(self.terminal.config.prompt).await;
+        let prompt = self.terminal.config.prompt;
+        self.read_line_with_prompt(reader, writer, || prompt, redraw_signal)
+            .await
+    }
+
+    /// Read a complete line, also reacting to window resizes
+    ///
+    /// `resize_signal` carries the new `(columns, rows)` of the terminal, e.g.
+    /// forwarded by a host or PTY bridge. The size is passed to
+    /// [`Terminal::handle_resize`] and the line is redrawn for the new width.
+    /// Terminals are assumed to rewrap the rows already on screen, as most
+    /// do, so the redraw starts from the row the prompt has moved to.
+    pub async fn read_line_with_resize<R, W, M>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        redraw_signal: Option<&Signal<M, ()>>,
+        resize_signal: Option<&Signal<M, (u16, u16)>>,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+    {
+        let prompt = self.terminal.config.prompt;
+        let wake = Wake {
+            resize: resize_signal,
+            ..Wake::new(redraw_signal)
+        };
+        self.read_line_inner(reader, writer, || prompt, wake).await
+    }
+
+    /// Read a complete line, computing the prompt each time it is drawn
+    ///
+    /// `prompt` replaces [`TerminalConfig::prompt`] for this read. It is called
+    /// for the initial prompt and on every redraw, so it can return an owned
+    /// string such as a `heapless::String` showing a counter or
+    /// connection state.
+    pub async fn read_line_with_prompt<R, W, M, F, P>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: F,
+        redraw_signal: Option<&Signal<M, ()>>,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+        F: FnMut() -> P,
+        P: AsRef<str>,
+    {
+        self.read_line_inner(reader, writer, prompt, Wake::new(redraw_signal))
+            .await
+    }
+
+    /// Read a complete line showing `prompt` instead of
+    /// [`TerminalConfig::prompt`], e.g. to ask a one-off question
+    pub async fn read_line_prompt<R, W, M>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        redraw_signal: Option<&Signal<M, ()>>,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+    {
+        self.read_line_with_prompt(reader, writer, || prompt, redraw_signal)
+            .await
+    }
+
+    /// Read a complete line, giving up when `timeout` completes first
+    ///
+    /// `timeout` is any future, e.g. an `embassy_time::Timer`; it is only
+    /// raced against waiting for input, never against drawing. When it wins,
+    /// [`ReadLineError::Timeout`] is returned and the text typed so far is
+    /// kept: the next read draws the prompt followed by that text again.
+    /// Print a newline before other output so it doesn't land on the prompt.
+    pub async fn read_line_with_timeout<R, W, M, T>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        redraw_signal: Option<&Signal<M, ()>>,
+        timeout: T,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+        T: Future<Output = ()>,
+    {
+        let prompt = self.terminal.config.prompt;
+        let wake = Wake {
+            redraw: redraw_signal,
+            resize: None,
+            timeout,
+            tick: future::pending,
+            on_idle: || false,
+        };
+        self.read_line_inner(reader, writer, || prompt, wake).await
+    }
+
+    /// Read a complete line, calling `on_idle` whenever no input arrived for
+    /// a while
+    ///
+    /// `tick` creates the idle delay, e.g. `|| Timer::after_millis(500)`; a
+    /// new delay starts each time the reader begins waiting for input. When
+    /// it completes first, `on_idle` runs and the line, including a fresh
+    /// `prompt`, is redrawn if it returns `true`. This suits prompts showing
+    /// a clock, which would otherwise only update on key presses.
+    pub async fn read_line_with_idle<R, W, M, F, P, K, T, I>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: F,
+        redraw_signal: Option<&Signal<M, ()>>,
+        tick: K,
+        on_idle: I,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+        F: FnMut() -> P,
+        P: AsRef<str>,
+        K: FnMut() -> T,
+        T: Future<Output = ()>,
+        I: FnMut() -> bool,
+    {
+        let wake = Wake {
+            redraw: redraw_signal,
+            resize: None,
+            timeout: future::pending(),
+            tick,
+            on_idle,
+        };
+        self.read_line_inner(reader, writer, prompt, wake).await
+    }
+
+    /// Read a single key press, e.g. for menus and confirmations
+    ///
+    /// Escape sequences and UTF-8 characters are decoded as for
+    /// [`read_line`](Self::read_line), but nothing is echoed and the line
+    /// buffer and history are left untouched. Bytes after the key stay
+    /// queued for the next read.
+    pub async fn read_key<R: Read>(&mut self, reader: &mut R) -> Result<KeyCode, ReadLineError> {
+        loop {
+            let byte = self.next_byte(reader).await?;
+            if let Some(key) = self.terminal.process_byte(byte) {
+                return Ok(key);
+            }
+        }
+    }
+
+    /// Read the next key press, apply it to the line buffer and report what
+    /// happened
+    ///
+    /// A building block for custom input loops: nothing is drawn, and the
+    /// event comes straight from [`Terminal::handle_key`], so history
+    /// navigation and line continuation are left to the caller. On
+    /// [`TerminalEvent::CommandReady`] the line is still in the buffer; get
+    /// it with [`Terminal::take_command`] through
+    /// [`terminal_mut`](Self::terminal_mut).
+    pub async fn next_event<R: Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<TerminalEvent, ReadLineError> {
+        let key = self.read_key(reader).await?;
+        Ok(self.terminal.handle_key(key))
+    }
+
+    /// Take the next input byte, reading a new batch when the last one is used up
+    async fn next_byte<R: Read>(&mut self, reader: &mut R) -> Result<u8, ReadLineError> {
+        if self.input_pos == self.input_len {
+            let len = reader
+                .read(&mut self.input)
+                .await
+                .map_err(|_| ReadLineError::IoError)?;
+            if len == 0 {
+                return Err(ReadLineError::EndOfFile);
+            }
+            self.input_pos = 0;
+            self.input_len = len;
+        }
+        let byte = self.input[self.input_pos];
+        self.input_pos += 1;
+        Ok(byte)
+    }
+
+    /// Ask a yes/no question and wait for the answer
+    ///
+    /// Writes `prompt [Y/n] ` (or `[y/N]` when `default` is `false`), then
+    /// reads keys until `y` or `n` in either case, or Enter for the default.
+    /// Ctrl+C answers no and Ctrl+D ends with [`ReadLineError::EndOfFile`];
+    /// other keys are ignored. The answer is echoed, followed by a newline.
+    pub async fn confirm<R, W>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        default: bool,
+    ) -> Result<bool, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+    {
+        let choices = if default { " [Y/n] " } else { " [y/N] " };
+        writer.write_str(prompt).await.map_err(|_| ReadLineError::IoError)?;
+        writer.write_str(choices).await.map_err(|_| ReadLineError::IoError)?;
+        writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+        let answer = loop {
+            match self.read_key(reader).await? {
+                KeyCode::Char(b'y' | b'Y') => break true,
+                KeyCode::Char(b'n' | b'N') | KeyCode::CtrlC => break false,
+                KeyCode::Enter => break default,
+                KeyCode::CtrlD => return Err(ReadLineError::EndOfFile),
+                _ => {}
+            }
+        };
+        writer
+            .write_str(if answer { "y\r\n" } else { "n\r\n" })
+            .await
+            .map_err(|_| ReadLineError::IoError)?;
+        writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+        Ok(answer)
+    }
 
-        let mut byte_buf = [0u8; 1];
+    /// Let the user pick one of `items` with the arrow keys
+    ///
+    /// The list is drawn one item per line with the highlighted item, first
+    /// `initial`, in reverse video; Up and Down move the highlight and Enter
+    /// returns its index. With ANSI support only the two affected lines are
+    /// rewritten on a move. Without it the current item is marked with `>`
+    /// and the whole list is written again. Ctrl+C, or an empty list, ends
+    /// with [`ReadLineError::Interrupted`].
+    pub async fn select<R, W>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        items: &[&str],
+        initial: usize,
+    ) -> Result<usize, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+    {
+        if items.is_empty() {
+            return Err(ReadLineError::Interrupted);
+        }
+        let mut current = initial.min(items.len() - 1);
+        write_menu(writer, items, current).await?;
 
         loop {
-            let event = if let Some(signal) = redraw_signal {
-                // Wait for either input or redraw signal
-                match select(reader.read(&mut byte_buf), signal.wait()).await {
-                    Either::First(Ok(1)) => {
-                        if let Some(key) = self.terminal.process_byte(byte_buf[0]) {
-                            self.terminal.handle_key(key)
-                        } else {
-                            TerminalEvent::None
-                        }
+            writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+            let next = match self.read_key(reader).await? {
+                KeyCode::Enter => return Ok(current),
+                KeyCode::CtrlC => return Err(ReadLineError::Interrupted),
+                KeyCode::CtrlD => return Err(ReadLineError::EndOfFile),
+                KeyCode::ArrowUp => current.saturating_sub(1),
+                KeyCode::ArrowDown => (current + 1).min(items.len() - 1),
+                _ => continue,
+            };
+            if next == current {
+                continue;
+            }
+            if writer.ansi_enabled() {
+                // The cursor rests on the line below the list
+                let io = |_| ReadLineError::IoError;
+                writer.cursor_up(items.len() - current).await.map_err(io)?;
+                write_menu_item(writer, items[current], false).await?;
+                if next < current {
+                    writer.cursor_up(current - next).await.map_err(io)?;
+                } else {
+                    writer.cursor_down(next - current).await.map_err(io)?;
+                }
+                write_menu_item(writer, items[next], true).await?;
+                writer.cursor_down(items.len() - next).await.map_err(io)?;
+                writer.write_str("\r").await.map_err(io)?;
+            } else {
+                writer.write_str("\r\n").await.map_err(|_| ReadLineError::IoError)?;
+                write_menu(writer, items, next).await?;
+            }
+            current = next;
+        }
+    }
+
+    /// Read a number, prompting again until the line parses as `T`
+    ///
+    /// The line is edited as with [`read_line`](Self::read_line) but not
+    /// added to the history. Invalid input is reported with
+    /// [`TerminalWriter::write_error`] before prompting again, and Ctrl+C
+    /// ends with [`ReadLineError::Interrupted`].
+    pub async fn read_number<T, R, W>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+    ) -> Result<T, ReadLineError>
+    where
+        T: FromStr,
+        R: Read,
+        W: AsyncWrite,
+    {
+        self.read_number_if(reader, writer, prompt, |_| true).await
+    }
+
+    /// Read a number within `range`, see [`read_number`](Self::read_number)
+    pub async fn read_number_in<T, R, W>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        range: RangeInclusive<T>,
+    ) -> Result<T, ReadLineError>
+    where
+        T: FromStr + PartialOrd,
+        R: Read,
+        W: AsyncWrite,
+    {
+        self.read_number_if(reader, writer, prompt, |n| range.contains(n)).await
+    }
+
+    async fn read_number_if<T, R, W, C>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        accept: C,
+    ) -> Result<T, ReadLineError>
+    where
+        T: FromStr,
+        R: Read,
+        W: AsyncWrite,
+        C: Fn(&T) -> bool,
+    {
+        let guard = PromptGuard::new(self);
+        let no_signal: Option<&Signal<NoopRawMutex, ()>> = None;
+        loop {
+            let line = guard.reader.read_line_prompt(reader, writer, prompt, no_signal).await?;
+            let message = match line.trim().parse::<T>() {
+                Ok(number) if accept(&number) => return Ok(number),
+                Ok(_) => "Out of range",
+                Err(_) => "Invalid number",
+            };
+            writer.write_error(message).await.map_err(|_| ReadLineError::IoError)?;
+            // Without ANSI the message is already written as a line
+            if writer.ansi_enabled() {
+                writer.write_str("\r\n").await.map_err(|_| ReadLineError::IoError)?;
+            }
+        }
+    }
+
+    /// Run [`read_line_events`](Self::read_line_events), dropping the
+    /// lines of a continued command when the read fails
+    ///
+    /// Only a timeout keeps them, like it keeps the text typed so far.
+    async fn read_line_inner<R, W, M, F, P, T, K, D, I>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: F,
+        wake: Wake<'_, M, T, K, I>,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+        F: FnMut() -> P,
+        P: AsRef<str>,
+        T: Future<Output = ()>,
+        K: FnMut() -> D,
+        D: Future<Output = ()>,
+        I: FnMut() -> bool,
+    {
+        let result = self.read_line_events(reader, writer, prompt, wake).await;
+        if result.is_err() && !matches!(result, Err(ReadLineError::Timeout)) {
+            self.continued = None;
+        }
+        result
+    }
+
+    async fn read_line_events<R, W, M, F, P, T, K, D, I>(
+        &mut self,
+        reader: &mut R,
+        writer: &mut TerminalWriter<'_, W>,
+        mut prompt: F,
+        wake: Wake<'_, M, T, K, I>,
+    ) -> Result<String<BUF_SIZE>, ReadLineError>
+    where
+        R: Read,
+        W: AsyncWrite,
+        M: RawMutex,
+        F: FnMut() -> P,
+        P: AsRef<str>,
+        T: Future<Output = ()>,
+        K: FnMut() -> D,
+        D: Future<Output = ()>,
+        I: FnMut() -> bool,
+    {
+        let Wake {
+            redraw: redraw_signal,
+            resize: resize_signal,
+            timeout,
+            mut tick,
+            mut on_idle,
+        } = wake;
+        let mut timeout = core::pin::pin!(timeout);
+        // Display initial prompt
+        let mut shown = prompt();
+        // Only the first line is shared with output left of the prompt
+        let mut offset = self.terminal.config.line_offset;
+        self.start_line();
+        if self.terminal.buffer.is_empty() {
+            writer
+                .write_prompt(self.active_prompt(shown.as_ref()))
+                .await
+                .map_err(|_| ReadLineError::IoError)?;
+        } else {
+            // Text kept from a read that timed out
+            self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
+        }
+
+        loop {
+            if self.input_pos == self.input_len {
+                writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+                // Wait for input or one of the signals; signals are only
+                // handled between batches
+                let wait = select4(
+                    reader.read(&mut self.input),
+                    wait_signal(redraw_signal),
+                    wait_signal(resize_signal),
+                    select(timeout.as_mut(), tick()),
+                );
+                match wait.await {
+                    Either4::First(Ok(0)) => return Err(ReadLineError::EndOfFile),
+                    Either4::First(Ok(len)) => {
+                        self.input_pos = 0;
+                        self.input_len = len;
                     }
-                    Either::Second(_) => {
+                    Either4::First(Err(_)) => return Err(ReadLineError::IoError),
+                    Either4::Second(()) => {
                         // Redraw requested
-                        signal.reset();
-                        writer.clear_line().await;
-                        writer.write_prompt(self.terminal.config.prompt).await;
-                        writer.write_str(self.terminal.buffer_str().unwrap_or("")).await;
+                        shown = prompt();
+                        self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
                         continue;
                     }
-                    _ => continue,
-                }
-            } else {
-                // Simple read without redraw support
-                match reader.read(&mut byte_buf).await {
-                    Ok(1) => {
-                        if let Some(key) = self.terminal.process_byte(byte_buf[0]) {
-                            self.terminal.handle_key(key)
-                        } else {
-                            TerminalEvent::None
+                    Either4::Third((columns, rows)) => {
+                        let event = self.terminal.handle_resize(columns, rows);
+                        if event == TerminalEvent::BufferChanged {
+                            shown = prompt();
+                            self.rewrap(self.active_prompt(shown.as_ref()), offset);
+                            self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
                         }
+                        continue;
+                    }
+                    Either4::Fourth(Either::First(())) => return Err(ReadLineError::Timeout),
+                    Either4::Fourth(Either::Second(())) => {
+                        if on_idle() {
+                            shown = prompt();
+                            self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
+                        }
+                        continue;
                     }
-                    _ => continue,
                 }
-            };
+            }
 
-            match event {
-                TerminalEvent::CommandReady => {
-                    writer.write_str("\r\n").await;
-                    let command = self.terminal.take_command()?;
-                    
-                    // Add to history if available
-                    if let Some(ref mut hist) = self.history {
-                        let _ = hist.add(&command);
-                    }
-                    
-                    return Ok(command);
+            let byte = self.input[self.input_pos];
+            self.input_pos += 1;
+
+            match self.feed(byte)? {
+                ReadStep::None | ReadStep::Custom(_) | ReadStep::Ctrl(_) => {}
+                ReadStep::Redraw => {
+                    shown = prompt();
+                    self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
                 }
-                TerminalEvent::EmptyCommand => {
-                    writer.write_str("\r\n").await;
-                    writer.write_prompt(self.terminal.config.prompt).await;
+                ReadStep::MoveCursor => {
+                    self.place_cursor(writer, self.active_prompt(shown.as_ref()), offset).await?;
                 }
-                TerminalEvent::BufferChanged => {
-                    if self.terminal.config.echo {
-                        // Redraw the line
-                        writer.clear_line().await;
-                        writer.write_prompt(self.terminal.config.prompt).await;
-                        writer.write_str(self.terminal.buffer_str().unwrap_or("")).await;
+                ReadStep::NewLine => {
+                    offset = 0;
+                    self.start_line();
+                    writer
+                        .write_str("\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    shown = prompt();
+                    writer
+                        .write_prompt(self.active_prompt(shown.as_ref()))
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                }
+                ReadStep::Interrupted => {
+                    offset = 0;
+                    self.move_to_last_row(writer).await?;
+                    self.start_line();
+                    writer
+                        .write_str("^C\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    if self.interruptible {
+                        writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+                        return Err(ReadLineError::Interrupted);
                     }
+                    shown = prompt();
+                    writer
+                        .write_prompt(self.active_prompt(shown.as_ref()))
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
                 }
-                TerminalEvent::Interrupt => {
-                    self.terminal.clear_buffer();
-                    writer.write_str("^C\r\n").await;
-                    writer.write_prompt(self.terminal.config.prompt).await;
+                ReadStep::Continuation => {
+                    offset = 0;
+                    self.move_to_last_row(writer).await?;
+                    self.start_line();
+                    writer
+                        .write_str("\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    writer
+                        .write_prompt(self.terminal.config.continuation_prompt)
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
                 }
-                TerminalEvent::EndOfFile => {
-                    return Err(ReadLineError::EndOfFile);
+                ReadStep::ListCompletions => {
+                    offset = 0;
+                    self.move_to_last_row(writer).await?;
+                    self.start_line();
+                    writer
+                        .write_str("\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    completion::write_candidates(
+                        writer,
+                        self.terminal.completions(),
+                        self.terminal.config.completion.layout,
+                        self.terminal.config.width,
+                    )
+                    .await
+                    .map_err(|_| ReadLineError::IoError)?;
+                    shown = prompt();
+                    self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
                 }
-                TerminalEvent::HistoryPrevious => {
-                    if let Some(ref mut hist) = self.history {
-                        if let Some(entry) = hist.previous() {
-                            let _ = self.terminal.set_buffer(entry);
-                            // Redraw the line
-                            writer.clear_line().await;
-                            writer.write_prompt(self.terminal.config.prompt).await;
-                            writer.write_str(self.terminal.buffer_str().unwrap_or("")).await;
-                        }
+                ReadStep::Bell => {
+                    writer.bell().await.map_err(|_| ReadLineError::IoError)?;
+                }
+                ReadStep::Rejected(message) => {
+                    offset = 0;
+                    self.move_to_last_row(writer).await?;
+                    self.start_line();
+                    writer
+                        .write_str("\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    writer
+                        .write_error(message)
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    // Without ANSI the message is already written as a line
+                    if writer.ansi_enabled() {
+                        writer
+                            .write_str("\r\n")
+                            .await
+                            .map_err(|_| ReadLineError::IoError)?;
+                    }
+                    shown = prompt();
+                    self.redraw(writer, self.active_prompt(shown.as_ref()), offset).await?;
+                }
+                ReadStep::Submitted(command) => {
+                    self.move_to_last_row(writer).await?;
+                    writer
+                        .write_str("\r\n")
+                        .await
+                        .map_err(|_| ReadLineError::IoError)?;
+                    let queued = self.take_queued_output();
+                    if !queued.is_empty() {
+                        writer
+                            .write_str(&queued)
+                            .await
+                            .map_err(|_| ReadLineError::IoError)?;
                     }
+                    writer.flush().await.map_err(|_| ReadLineError::IoError)?;
+                    return Ok(command);
+                }
+            }
+        }
+    }
+
+    /// Process one input byte without doing any I/O
+    ///
+    /// This is the editing logic of [`read_line`](Self::read_line) as a plain
+    /// state machine, for bare-metal loops without an async executor: poll the
+    /// UART, push each byte here and perform the returned [`ReadStep`] on
+    /// screen. History navigation and submitting are handled as in
+    /// `read_line`. Ctrl+D on an empty line yields
+    /// [`ReadLineError::EndOfFile`].
+    pub fn feed(&mut self, byte: u8) -> Result<ReadStep<BUF_SIZE>, ReadLineError> {
+        let Some(key) = self.terminal.process_byte(byte) else {
+            return Ok(ReadStep::None);
+        };
+        let step = match self.terminal.handle_key(key) {
+            TerminalEvent::CommandReady => {
+                let line = self.terminal.take_command()?;
+                // Raw lines are taken as they are
+                let continuation = if self.terminal.is_raw_mode() {
+                    None
+                } else {
+                    strip_continuation(&line)
+                };
+                let (text, more) = match continuation {
+                    Some(head) => (head, true),
+                    None => (line.as_str(), false),
+                };
+                let mut command = self.continued.take().unwrap_or_default();
+                if command.push_str(text).is_err() {
+                    // Only possible while continuing; keep editing the last line
+                    self.continued = Some(command);
+                    let _ = self.terminal.set_buffer(&line);
+                    return Ok(ReadStep::Bell);
                 }
-                TerminalEvent::HistoryNext => {
+                if more {
+                    self.continued = Some(command);
+                    return Ok(ReadStep::Continuation);
+                }
+                self.submit(command)
+            }
+            TerminalEvent::EmptyCommand => match self.continued.take() {
+                Some(command) if !command.is_empty() => self.submit(command),
+                _ => {
+                    self.search = None;
                     if let Some(ref mut hist) = self.history {
-                        if let Some(entry) = hist.next() {
-                            let _ = self.terminal.set_buffer(entry);
-                        } else {
-                            // At the end of history, clear buffer
-                            self.terminal.clear_buffer();
-                        }
-                        // Redraw the line
-                        writer.clear_line().await;
-                        writer.write_prompt(self.terminal.config.prompt).await;
-                        writer.write_str(self.terminal.buffer_str().unwrap_or("")).await;
+                        hist.reset_position();
                     }
+                    ReadStep::NewLine
                 }
-                TerminalEvent::BufferFull => {
-                    // Optionally signal buffer full (beep?)
+            },
+            TerminalEvent::BufferChanged if self.terminal.config.echo => ReadStep::Redraw,
+            TerminalEvent::CursorMoved if self.terminal.config.echo => ReadStep::MoveCursor,
+            TerminalEvent::Interrupt => {
+                // Aborts all lines of a continued command
+                self.continued = None;
+                self.search = None;
+                if let Some(ref mut hist) = self.history {
+                    hist.reset_position();
                 }
-                _ => {}
+                self.terminal.clear_buffer();
+                ReadStep::Interrupted
+            }
+            TerminalEvent::EndOfFile => return Err(ReadLineError::EndOfFile),
+            TerminalEvent::HistoryPrevious => self.history_previous(),
+            TerminalEvent::HistoryNext => self.history_next(),
+            TerminalEvent::CompletionList => ReadStep::ListCompletions,
+            TerminalEvent::Bell => ReadStep::Bell,
+            TerminalEvent::Custom(action) => ReadStep::Custom(action),
+            TerminalEvent::Ctrl(letter) => ReadStep::Ctrl(letter),
+            TerminalEvent::BufferFull if self.terminal.config.bell_on_full => ReadStep::Bell,
+            _ => ReadStep::None,
+        };
+        Ok(step)
+    }
+
+    /// Recall an older entry starting with the text before the cursor
+    ///
+    /// The line being edited is saved when navigation starts, and an empty
+    /// prefix walks through all entries.
+    fn history_previous(&mut self) -> ReadStep<BUF_SIZE> {
+        let Some(ref mut hist) = self.history else {
+            return ReadStep::None;
+        };
+        let terminal = &self.terminal;
+        let search = self.search.get_or_insert_with(|| {
+            let mut line = String::new();
+            let _ = line.push_str(terminal.buffer_str().unwrap_or(""));
+            HistorySearch {
+                prefix_len: terminal.cursor_byte_offset().min(line.len()),
+                line,
+            }
+        });
+        let prefix = &search.line[..search.prefix_len];
+        let entry = if prefix.is_empty() {
+            hist.previous()
+        } else {
+            hist.previous_with_prefix(prefix)
+        };
+        match entry {
+            Some(entry) => {
+                let _ = self.terminal.set_buffer(entry);
+                ReadStep::Redraw
+            }
+            None => {
+                if hist.position().is_none() {
+                    // Nothing matched, so navigation never started
+                    self.search = None;
+                }
+                ReadStep::None
             }
         }
     }
-}
 
-/// Errors that can occur while reading a line
-#[derive(Debug, Clone, Copy)]
-pub enum ReadLineError {
-    IoError,
-    Utf8Error,
-    EndOfFile,
-}
+    /// Recall a newer entry, restoring the saved line past the newest match
+    ///
+    /// Without an active navigation the line being edited is left alone.
+    fn history_next(&mut self) -> ReadStep<BUF_SIZE> {
+        let (Some(hist), Some(search)) = (self.history.as_mut(), self.search.as_ref()) else {
+            return ReadStep::None;
+        };
+        let prefix = &search.line[..search.prefix_len];
+        let entry = if prefix.is_empty() {
+            hist.next()
+        } else {
+            hist.next_with_prefix(prefix)
+        };
+        match entry {
+            Some(entry) => {
+                let _ = self.terminal.set_buffer(entry);
+            }
+            None => {
+                let _ = self.terminal.set_buffer(&search.line);
+                // Back where editing left off
+                self.terminal.cursor_pos = search.prefix_len;
+                self.search = None;
+            }
+        }
+        ReadStep::Redraw
+    }
 
-impl From<()> for ReadLineError {
-    fn from(_: ()) -> Self {
-        ReadLineError::Utf8Error
+    /// Validate a finished command, then add it to the history and report it
+    fn submit(&mut self, command: String<BUF_SIZE>) -> ReadStep<BUF_SIZE> {
+        self.search = None;
+        if let Some(validate) = self.terminal.config.validator {
+            if let Err(message) = validate(&command) {
+                if let Some(ref mut hist) = self.history {
+                    hist.reset_position();
+                }
+                let _ = self.terminal.set_buffer(&command);
+                return ReadStep::Rejected(message);
+            }
+        }
+        // Add to history if available
+        if let Some(ref mut hist) = self.history {
+            let _ = hist.add(&command);
+        }
+        ReadStep::Submitted(command)
+    }
+
+    /// Check whether a command continued with a trailing `\` is being read
+    pub fn is_continuing(&self) -> bool {
+        self.continued.is_some()
+    }
+
+    /// Pick the continuation prompt while continuing a command
+    fn active_prompt<'a>(&self, prompt: &'a str) -> &'a str {
+        if self.is_continuing() {
+            self.terminal.config.continuation_prompt
+        } else {
+            prompt
+        }
+    }
+
+    /// Take the output queued with [`queue_output`](Self::queue_output)
+    pub fn take_queued_output(&mut self) -> String<BUF_SIZE> {
+        core::mem::take(&mut self.deferred)
+    }
+
+    /// Get the line editor, e.g. to draw its buffer after a [`ReadStep`]
+    pub fn terminal(&self) -> &Terminal<BUF_SIZE> {
+        &self.terminal
+    }
+
+    /// Get mutable access to the line editor, e.g. to switch raw mode
+    pub fn terminal_mut(&mut self) -> &mut Terminal<BUF_SIZE> {
+        &mut self.terminal
+    }
+
+    /// Clear the current line and redraw the prompt followed by the buffer
+    ///
+    /// With a known [`TerminalConfig::width`] the input may span several
+    /// screen rows; the cursor first returns to the row the prompt starts on
+    /// and everything below it is cleared.
+    async fn redraw<W: AsyncWrite>(
+        &mut self,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        offset: u16,
+    ) -> Result<(), ReadLineError> {
+        let width = self.wrap_width(writer);
+        if width.is_some() {
+            writer
+                .cursor_up(self.cursor_row)
+                .await
+                .map_err(|_| ReadLineError::IoError)?;
+        }
+        self.start_line();
+        if offset == 0 {
+            writer.clear_line().await
+        } else {
+            writer.clear_line_from(offset + 1).await
+        }
+        .map_err(|_| ReadLineError::IoError)?;
+        if width.is_some() {
+            writer.clear_to_end().await.map_err(|_| ReadLineError::IoError)?;
+        }
+        writer
+            .write_prompt(prompt)
+            .await
+            .map_err(|_| ReadLineError::IoError)?;
+        let buffer = self.terminal.buffer_str().unwrap_or("");
+        match self.terminal.config.highlighter {
+            Some(highlighter) => highlight::write_highlighted(writer, buffer, highlighter).await,
+            None => writer.write_str(buffer).await,
+        }
+        .map_err(|_| ReadLineError::IoError)?;
+        if let Some(width) = width {
+            let end = offset as usize + display_width(prompt) + self.terminal.display_width();
+            if end > 0 && end % width == 0 {
+                // Terminals leave the cursor on the last column of a full
+                // row until the next character; move it down explicitly
+                writer
+                    .write_str("\r\n")
+                    .await
+                    .map_err(|_| ReadLineError::IoError)?;
+            }
+            self.cursor_row = end / width;
+            self.last_row = end / width;
+        }
+        if self.terminal.cursor_byte_offset() < self.terminal.buffer.len() {
+            self.place_cursor(writer, prompt, offset).await?;
+        }
+        Ok(())
+    }
+
+    /// Move the on-screen cursor to the editing position
+    async fn place_cursor<W: AsyncWrite>(
+        &mut self,
+        writer: &mut TerminalWriter<'_, W>,
+        prompt: &str,
+        offset: u16,
+    ) -> Result<(), ReadLineError> {
+        let pos = offset as usize + display_width(prompt) + self.terminal.cursor_column();
+        let col = match self.wrap_width(writer) {
+            Some(width) => {
+                let row = pos / width;
+                if row < self.cursor_row {
+                    writer.cursor_up(self.cursor_row - row).await
+                } else {
+                    writer.cursor_down(row - self.cursor_row).await
+                }
+                .map_err(|_| ReadLineError::IoError)?;
+                self.cursor_row = row;
+                pos % width + 1
+            }
+            None => pos + 1,
+        };
+        writer
+            .move_cursor_col(col as u16)
+            .await
+            .map_err(|_| ReadLineError::IoError)
+    }
+
+    /// Recompute the cursor's row for the current width, after the terminal
+    /// rewrapped the rows of the input
+    fn rewrap(&mut self, prompt: &str, offset: u16) {
+        if let Some(width) = self.terminal.config.width.filter(|&width| width > 0) {
+            let start = offset as usize + display_width(prompt);
+            let width = width as usize;
+            self.cursor_row = (start + self.terminal.cursor_column()) / width;
+            self.last_row = (start + self.terminal.display_width()) / width;
+        }
     }
-}
\ No newline at end of file
+
+    /// Move the cursor below the end of the input before starting a new line
+    async fn move_to_last_row<W: AsyncWrite>(
+        &mut self,
+        writer: &mut TerminalWriter<'_, W>,
+    ) -> Result<(), ReadLineError> {
+        if self.last_row > self.cursor_row {
+            writer
+                .cursor_down(self.last_row - self.cursor_row)
+                .await
+                .map_err(|_| ReadLineError::IoError)?;
+            self.cursor_row = self.last_row;
+        }
+        Ok(())
+    }
+
+    /// Forget the rows of the previous input once a fresh prompt is written
+    fn start_line(&mut self) {
+        self.cursor_row = 0;
+        self.last_row = 0;
+    }
+
+    /// Width to wrap at, if known and the writer can move the cursor
+    fn wrap_width<W: AsyncWrite>(&self, writer: &TerminalWriter<'_, W>) -> Option<usize> {
+        match self.terminal.config.width {
+            Some(width) if width > 0 && writer.ansi_enabled() => Some(width as usize),
+            _ => None,
+        }
+    }
+}
+
+/// Strip the `\` of a line that continues on the next one
+///
+/// An escaped backslash (`\\`) at the end does not continue the line.
+fn strip_continuation(line: &str) -> Option<&str> {
+    let trailing = line.bytes().rev().take_while(|&b| b == b'\\').count();
+    if trailing % 2 == 1 {
+        Some(&line[..line.len() - 1])
+    } else {
+        None
+    }
+}
+
+/// Write a whole menu for [`TerminalReader::select`], one item per line
+async fn write_menu<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    items: &[&str],
+    current: usize,
+) -> Result<(), ReadLineError> {
+    for (i, item) in items.iter().enumerate() {
+        write_menu_item(writer, item, i == current).await?;
+        writer.write_str("\r\n").await.map_err(|_| ReadLineError::IoError)?;
+    }
+    Ok(())
+}
+
+/// Write one menu item from the start of the line, leaving the cursor after it
+async fn write_menu_item<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    item: &str,
+    selected: bool,
+) -> Result<(), ReadLineError> {
+    let io = |_| ReadLineError::IoError;
+    writer.write_str("\r").await.map_err(io)?;
+    if !writer.ansi_enabled() {
+        writer.write_str(if selected { "> " } else { "  " }).await.map_err(io)?;
+        writer.write_str(item).await.map_err(io)
+    } else if selected {
+        writer.set_reverse(true).await.map_err(io)?;
+        writer.write_str(item).await.map_err(io)?;
+        writer.set_reverse(false).await.map_err(io)
+    } else {
+        writer.write_str(item).await.map_err(io)
+    }
+}
+
+/// Sets the history aside and makes Ctrl+C interrupt the read while a
+/// one-off prompt such as [`TerminalReader::read_number`] runs
+///
+/// Both are restored on drop, so a prompt whose future is dropped, e.g. by a
+/// timeout raced against it, leaves the reader as it was.
+struct PromptGuard<'r, const BUF_SIZE: usize> {
+    reader: &'r mut TerminalReader<BUF_SIZE>,
+    history: Option<History<BUF_SIZE>>,
+    interruptible: bool,
+}
+
+impl<'r, const BUF_SIZE: usize> PromptGuard<'r, BUF_SIZE> {
+    fn new(reader: &'r mut TerminalReader<BUF_SIZE>) -> Self {
+        let history = reader.history.take();
+        let interruptible = core::mem::replace(&mut reader.interruptible, true);
+        Self {
+            reader,
+            history,
+            interruptible,
+        }
+    }
+}
+
+impl<const BUF_SIZE: usize> Drop for PromptGuard<'_, BUF_SIZE> {
+    fn drop(&mut self) {
+        self.reader.history = self.history.take();
+        self.reader.interruptible = self.interruptible;
+    }
+}
+
+/// Everything besides input that can wake a read waiting for input
+struct Wake<'s, M: RawMutex, T, K, I> {
+    redraw: Option<&'s Signal<M, ()>>,
+    resize: Option<&'s Signal<M, (u16, u16)>>,
+    /// Ends the read with [`ReadLineError::Timeout`]
+    timeout: T,
+    /// Creates the delay after which `on_idle` runs
+    tick: K,
+    /// Returns whether the line needs a redraw
+    on_idle: I,
+}
+
+impl<'s, M: RawMutex>
+    Wake<'s, M, future::Pending<()>, fn() -> future::Pending<()>, fn() -> bool>
+{
+    /// Wake only for `redraw`, which may be `None` as well
+    fn new(redraw: Option<&'s Signal<M, ()>>) -> Self {
+        Self {
+            redraw,
+            resize: None,
+            timeout: future::pending(),
+            tick: future::pending,
+            on_idle: || false,
+        }
+    }
+}
+
+/// Wait for a signal, or forever if there is none
+async fn wait_signal<M: RawMutex, T: Send>(signal: Option<&Signal<M, T>>) -> T {
+    match signal {
+        Some(signal) => signal.wait().await,
+        None => core::future::pending().await,
+    }
+}
+
+/// Errors that can occur while reading a line
+#[derive(Debug, Clone, Copy)]
+pub enum ReadLineError {
+    /// Reading input or writing output failed
+    IoError,
+    Utf8Error,
+    /// Ctrl+D was pressed on an empty line or the input ended (a read
+    /// returned 0 bytes)
+    EndOfFile,
+    /// Ctrl+C cancelled a [`TerminalReader::select`] or
+    /// [`TerminalReader::read_number`]
+    Interrupted,
+    /// No line was completed before the timeout, see
+    /// [`TerminalReader::read_line_with_timeout`]
+    Timeout,
+}
+
+impl core::fmt::Display for ReadLineError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            ReadLineError::IoError => "I/O error",
+            ReadLineError::Utf8Error => "input is not valid UTF-8",
+            ReadLineError::EndOfFile => "unexpected end of input",
+            ReadLineError::Timeout => "timed out waiting for input",
+            ReadLineError::Interrupted => "interrupted",
+        })
+    }
+}
+
+impl core::error::Error for ReadLineError {}
+
+impl From<()> for ReadLineError {
+    fn from(_: ()) -> Self {
+        ReadLineError::Utf8Error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{MockReader, MockWriter};
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use embassy_futures::block_on;
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    const NO_SIGNAL: Option<&Signal<NoopRawMutex, ()>> = None;
+
+    fn feed<const N: usize>(terminal: &mut Terminal<N>, bytes: &[u8]) -> Option<KeyCode> {
+        let mut key = None;
+        for &b in bytes {
+            key = terminal.process_byte(b);
+        }
+        key
+    }
+
+    #[test]
+    fn test_navigation_key_sequences() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        assert_eq!(feed(&mut terminal, b"\x1b[H"), Some(KeyCode::Home));
+        assert_eq!(feed(&mut terminal, b"\x1b[F"), Some(KeyCode::End));
+        assert_eq!(feed(&mut terminal, b"\x1bOH"), Some(KeyCode::Home));
+        assert_eq!(feed(&mut terminal, b"\x1b[1~"), Some(KeyCode::Home));
+        assert_eq!(feed(&mut terminal, b"\x1b[4~"), Some(KeyCode::End));
+        assert_eq!(feed(&mut terminal, b"\x1b[5~"), Some(KeyCode::PageUp));
+        assert_eq!(feed(&mut terminal, b"\x1b[6~"), Some(KeyCode::PageDown));
+        assert_eq!(feed(&mut terminal, b"\x1b[3~"), Some(KeyCode::Delete));
+        // The trailing '~' must not leak through as a character
+        assert_eq!(terminal.process_byte(b'a'), Some(KeyCode::Char(b'a')));
+    }
+
+    #[test]
+    fn test_home_end_move_cursor() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("hello").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Home), TerminalEvent::CursorMoved);
+        assert_eq!(terminal.cursor_position(), 0);
+        assert_eq!(terminal.handle_key(KeyCode::End), TerminalEvent::CursorMoved);
+        assert_eq!(terminal.cursor_position(), 5);
+        assert_eq!(terminal.handle_key(KeyCode::End), TerminalEvent::None);
+        assert_eq!(terminal.handle_key(KeyCode::PageUp), TerminalEvent::PageUp);
+    }
+
+    #[test]
+    fn test_replace_current_word_mid_line() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("send pe hello").unwrap();
+        // Place the cursor inside "pe"
+        terminal.handle_key(KeyCode::Home);
+        for _ in 0..6 {
+            terminal.handle_key(KeyCode::ArrowRight);
+        }
+        assert_eq!(terminal.word_at_cursor(), 5..7);
+        terminal.replace_current_word("peer").unwrap();
+        assert_eq!(terminal.buffer_str(), Ok("send peer hello"));
+        assert_eq!(terminal.cursor_position(), 9);
+    }
+
+    #[test]
+    fn test_replace_current_word_trailing() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("he").unwrap();
+        terminal.replace_current_word("help").unwrap();
+        assert_eq!(terminal.buffer_str(), Ok("help"));
+        assert_eq!(terminal.cursor_position(), 4);
+
+        terminal.set_buffer("send ").unwrap();
+        terminal.replace_current_word("peer").unwrap();
+        assert_eq!(terminal.buffer_str(), Ok("send peer"));
+    }
+
+    #[test]
+    fn test_replace_current_word_overflow() {
+        let mut terminal = Terminal::<8>::new(TerminalConfig::default());
+        terminal.set_buffer("ab cd").unwrap();
+        assert_eq!(terminal.replace_current_word("toolong"), Err(CapacityError));
+        assert_eq!(terminal.buffer_str(), Ok("ab cd"));
+    }
+
+    #[test]
+    fn test_remaining_capacity() {
+        let mut terminal = Terminal::<8>::new(TerminalConfig::default());
+        assert_eq!(terminal.remaining_capacity(), 8);
+        terminal.set_buffer("héllo").unwrap();
+        assert_eq!(terminal.remaining_capacity(), 2);
+        assert!(!terminal.is_full());
+        terminal.set_buffer("12345678").unwrap();
+        assert_eq!(terminal.remaining_capacity(), 0);
+        assert!(terminal.is_full());
+    }
+
+    #[test]
+    fn test_max_command_len() {
+        let config = TerminalConfig {
+            max_command_len: 4,
+            ..TerminalConfig::default()
+        };
+        let mut terminal = Terminal::<16>::new(config);
+        for &byte in b"abc" {
+            terminal.handle_key(KeyCode::Char(byte));
+        }
+        assert_eq!(terminal.remaining_capacity(), 1);
+        assert_eq!(terminal.handle_key(KeyCode::Unicode('é')), TerminalEvent::BufferFull);
+        assert_eq!(terminal.handle_key(KeyCode::Char(b'd')), TerminalEvent::BufferChanged);
+        assert!(terminal.is_full());
+        assert_eq!(terminal.handle_key(KeyCode::Char(b'e')), TerminalEvent::BufferFull);
+        assert_eq!(terminal.buffer_str(), Ok("abcd"));
+
+        // A longer line set directly can still be shortened
+        terminal.set_buffer("abcdefgh").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Backspace), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("abcdefg"));
+        assert_eq!(terminal.remaining_capacity(), 0);
+    }
+
+    #[test]
+    fn test_record_and_replay_keys() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.start_recording();
+        for &byte in b"lx\x08s -l\x1b[H" {
+            if let Some(key) = terminal.process_byte(byte) {
+                terminal.handle_key(key);
+            }
+        }
+        assert!(terminal.is_recording());
+        let keys = terminal.stop_recording().unwrap();
+        assert!(!terminal.is_recording());
+        assert_eq!(keys.len(), 8);
+        assert_eq!(keys[2], KeyCode::Backspace);
+
+        terminal.clear_buffer();
+        assert_eq!(terminal.replay(&keys), TerminalEvent::CursorMoved);
+        assert_eq!(terminal.buffer_str(), Ok("ls -l"));
+        assert_eq!(terminal.cursor_position(), 0);
+        assert_eq!(terminal.replay(&[KeyCode::Enter]), TerminalEvent::CommandReady);
+    }
+
+    #[test]
+    fn test_alt_keys() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        assert_eq!(terminal.process_byte(0x1b), None);
+        assert_eq!(terminal.process_byte(b'x'), Some(KeyCode::Alt(b'x')));
+        // Sequences and a lone Escape are unaffected
+        assert_eq!(terminal.process_byte(0x1b), None);
+        assert_eq!(terminal.process_byte(b'['), None);
+        assert_eq!(terminal.process_byte(b'D'), Some(KeyCode::ArrowLeft));
+        assert_eq!(terminal.process_byte(0x1b), None);
+        assert_eq!(terminal.process_byte(0x1b), Some(KeyCode::Escape));
+        assert_eq!(terminal.process_byte(b'b'), Some(KeyCode::Char(b'b')));
+    }
+
+    #[test]
+    fn test_alt_word_motion() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("set  led on").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Alt(b'b')), TerminalEvent::CursorMoved);
+        assert_eq!(terminal.cursor_position(), 9);
+        terminal.handle_key(KeyCode::Alt(b'b'));
+        terminal.handle_key(KeyCode::Alt(b'b'));
+        assert_eq!(terminal.cursor_position(), 0);
+        assert_eq!(terminal.handle_key(KeyCode::Alt(b'b')), TerminalEvent::None);
+        terminal.handle_key(KeyCode::Alt(b'f'));
+        assert_eq!(terminal.cursor_position(), 3);
+        terminal.handle_key(KeyCode::Alt(b'f'));
+        assert_eq!(terminal.cursor_position(), 8);
+    }
+
+    #[test]
+    fn test_tab_expands_to_spaces() {
+        let mut terminal = Terminal::<8>::new(TerminalConfig::default());
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::None);
+
+        let config = TerminalConfig {
+            tab_width: 4,
+            ..Default::default()
+        };
+        let mut terminal = Terminal::<8>::new(config);
+        terminal.set_buffer("ab").unwrap();
+        terminal.handle_key(KeyCode::ArrowLeft);
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("a    b"));
+        assert_eq!(terminal.cursor_position(), 5);
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferFull);
+        assert_eq!(terminal.buffer_str(), Ok("a    b"));
+    }
+
+    #[test]
+    fn test_raw_mode_takes_bytes_literally() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_raw_mode(true);
+        terminal.set_raw_terminator(b'\n');
+        for &byte in b"\x03a\x1b[D\x7f\r" {
+            let key = terminal.process_byte(byte).unwrap();
+            assert_eq!(key, KeyCode::Char(byte));
+            assert_eq!(terminal.handle_key(key), TerminalEvent::BufferChanged);
+        }
+        assert_eq!(terminal.process_byte(b'\n'), Some(KeyCode::Enter));
+        assert_eq!(terminal.handle_key(KeyCode::Enter), TerminalEvent::CommandReady);
+        assert_eq!(terminal.buffer_str(), Ok("\x03a\x1b[D\x7f\r"));
+
+        terminal.set_raw_mode(false);
+        assert!(!terminal.is_raw_mode());
+        assert_eq!(terminal.process_byte(0x03), Some(KeyCode::CtrlC));
+    }
+
+    #[test]
+    fn test_read_line_in_raw_mode() {
+        let config = TerminalConfig {
+            echo: false,
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        reader.terminal_mut().set_raw_mode(true);
+        reader.terminal_mut().set_raw_terminator(b';');
+        let mut input = MockReader::new(b"a\x08\\\r\n;");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "a\x08\\\r\n");
+    }
+
+    #[test]
+    fn test_next_event() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::chunked(b"ab\x1b[D\x03x\r", 3);
+        let mut events = Vec::<TerminalEvent, 8>::new();
+        while let Ok(event) = block_on(reader.next_event(&mut input)) {
+            events.push(event).unwrap();
+        }
+        assert_eq!(
+            events.as_slice(),
+            &[
+                TerminalEvent::BufferChanged,
+                TerminalEvent::BufferChanged,
+                TerminalEvent::CursorMoved,
+                TerminalEvent::Interrupt,
+                TerminalEvent::BufferChanged,
+                TerminalEvent::CommandReady,
+            ]
+        );
+        // Nothing was drawn or taken, so the buffer is intact
+        assert_eq!(reader.terminal().buffer_str(), Ok("axb"));
+    }
+
+    #[test]
+    fn test_empty_delete_policies() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        assert_eq!(terminal.handle_key(KeyCode::Backspace), TerminalEvent::None);
+        assert_eq!(terminal.handle_key(KeyCode::Delete), TerminalEvent::None);
+
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            empty_delete: EmptyDeletePolicy::Bell,
+            ..Default::default()
+        });
+        assert_eq!(terminal.handle_key(KeyCode::Backspace), TerminalEvent::Bell);
+        assert_eq!(terminal.handle_key(KeyCode::Delete), TerminalEvent::Bell);
+
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            empty_delete: EmptyDeletePolicy::EndOfFile,
+            ..Default::default()
+        });
+        assert_eq!(terminal.handle_key(KeyCode::Delete), TerminalEvent::EndOfFile);
+        // A non-empty line is edited normally
+        terminal.set_buffer("a").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Backspace), TerminalEvent::BufferChanged);
+    }
+
+    #[test]
+    fn test_cursor_byte_offset_with_multibyte_content() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("h\u{e9}\u{20ac}o").unwrap();
+        assert_eq!(terminal.cursor_position(), 4);
+        assert_eq!(terminal.cursor_byte_offset(), 7);
+
+        terminal.handle_key(KeyCode::Home);
+        terminal.handle_key(KeyCode::ArrowRight);
+        terminal.handle_key(KeyCode::ArrowRight);
+        assert_eq!(terminal.cursor_position(), 2);
+        assert_eq!(terminal.cursor_byte_offset(), 3);
+
+        terminal.handle_key(KeyCode::ArrowRight);
+        assert_eq!(terminal.cursor_position(), 3);
+        assert_eq!(terminal.cursor_byte_offset(), 6);
+
+        terminal.handle_key(KeyCode::ArrowLeft);
+        assert_eq!(terminal.cursor_byte_offset(), 3);
+    }
+
+    #[test]
+    fn test_read_line_places_cursor_after_mid_line_edit() {
+        let mut input = MockReader::new(b"ac\x1b[DbX\r");
+        let mut output = MockWriter::new();
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "abXc");
+        // After inserting 'b' before 'c' the cursor sits on column 2 + 2 + 1
+        assert!(output.as_str().contains("\r\x1b[K> abc\x1b[5G"));
+        assert!(output.as_str().ends_with("\r\x1b[K> abXc\x1b[6G\r\n"));
+    }
+
+    fn check_number(word: &str) -> WordCheck {
+        match word {
+            "one" => WordCheck::Replace("1"),
+            w if w.bytes().all(|b| b.is_ascii_digit()) => WordCheck::Accept,
+            _ => WordCheck::Reject,
+        }
+    }
+
+    #[test]
+    fn test_space_hook_rejects_invalid_token() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            space_hook: Some(check_number),
+            ..Default::default()
+        });
+        for &b in b"12" {
+            terminal.handle_key(KeyCode::Char(b));
+        }
+        assert_eq!(terminal.handle_key(KeyCode::Char(b' ')), TerminalEvent::BufferChanged);
+        terminal.handle_key(KeyCode::Char(b'x'));
+        assert_eq!(terminal.handle_key(KeyCode::Char(b' ')), TerminalEvent::Bell);
+        assert_eq!(terminal.buffer_str(), Ok("12 x"));
+    }
+
+    #[test]
+    fn test_space_hook_replaces_token() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            space_hook: Some(check_number),
+            ..Default::default()
+        });
+        for &b in b"one" {
+            terminal.handle_key(KeyCode::Char(b));
+        }
+        terminal.handle_key(KeyCode::Char(b' '));
+        assert_eq!(terminal.buffer_str(), Ok("1 "));
+        assert_eq!(terminal.cursor_position(), 2);
+    }
+
+    fn expand_greeting(word: &str) -> WordCheck {
+        match word {
+            "hi" => WordCheck::Replace("hello"),
+            _ => WordCheck::Accept,
+        }
+    }
+
+    #[test]
+    fn test_space_hook_replacement_without_room_for_space() {
+        let mut terminal = Terminal::<5>::new(TerminalConfig {
+            space_hook: Some(expand_greeting),
+            ..Default::default()
+        });
+        terminal.set_buffer("hi").unwrap();
+        // "hello" alone would fit, but not with the space after it
+        assert_eq!(terminal.handle_key(KeyCode::Char(b' ')), TerminalEvent::BufferFull);
+        assert_eq!(terminal.buffer_str(), Ok("hi"));
+        assert_eq!(terminal.cursor_position(), 2);
+    }
+
+    #[test]
+    fn test_write_config() {
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        block_on(TerminalConfig::default().write_config(&mut writer)).unwrap();
+        assert_eq!(
+            output.as_str(),
+            concat!(
+                "buffer_size:  128\r\n",
+                "prompt:       \"> \"\r\n",
+                "continuation: \"> \"\r\n",
+                "echo:         true\r\n",
+                "ansi_enabled: true\r\n",
+                "empty_delete: Silent\r\n",
+                "space_hook:   none\r\n",
+                "bell_on_full: true\r\n",
+                "width:        unknown\r\n",
+                "height:       unknown\r\n",
+                "line_offset:  0\r\n",
+                "completer:    none\r\n",
+                "completion:   Grid\r\n",
+                "ignore_case:  false\r\n",
+                "key_bindings: default\r\n",
+                "highlighter:  none\r\n",
+                "edit_mode:    Emacs\r\n",
+                "newline:      CrLf\r\n",
+                "validator:    none\r\n",
+                "ctrl_d_always_eof: false\r\n",
+                "tab_width:    0\r\n",
+                "max_command_len: none\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_line_rings_bell_when_full() {
+        let mut input = MockReader::new(b"abc\r");
+        let mut output = MockWriter::new();
+        let mut reader = TerminalReader::<2>::new(TerminalConfig::default(), None);
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "ab");
+        assert!(output.as_str().ends_with("> ab\x07\r\n"));
+
+        let mut input = MockReader::new(b"abc\r");
+        let mut output = MockWriter::new();
+        let config = TerminalConfig {
+            bell_on_full: false,
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<2>::new(config, None);
+        {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        }
+        assert!(!output.as_str().contains('\x07'));
+    }
+
+    #[test]
+    fn test_multibyte_input() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        assert_eq!(terminal.process_byte(0xC3), None);
+        assert_eq!(terminal.process_byte(0xA9), Some(KeyCode::Unicode('\u{e9}')));
+        terminal.handle_key(KeyCode::Unicode('\u{e9}'));
+        assert_eq!(terminal.buffer_str(), Ok("\u{e9}"));
+        assert_eq!(terminal.cursor_position(), 1);
+    }
+
+    #[test]
+    fn test_delete_whole_multibyte_char() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.handle_key(KeyCode::Unicode('\u{e9}'));
+        assert_eq!(terminal.handle_key(KeyCode::Backspace), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok(""));
+        assert_eq!(terminal.cursor_position(), 0);
+
+        terminal.set_buffer("a\u{20ac}b").unwrap();
+        terminal.handle_key(KeyCode::Home);
+        terminal.handle_key(KeyCode::ArrowRight);
+        assert_eq!(terminal.handle_key(KeyCode::Delete), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("ab"));
+        assert_eq!(terminal.cursor_position(), 1);
+        terminal.handle_key(KeyCode::End);
+        terminal.handle_key(KeyCode::Unicode('\u{20ac}'));
+        terminal.handle_key(KeyCode::Backspace);
+        assert_eq!(terminal.buffer_str(), Ok("ab"));
+    }
+
+    #[test]
+    fn test_split_multibyte_char_then_enter() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        for &b in b"ab" {
+            let key = terminal.process_byte(b).unwrap();
+            terminal.handle_key(key);
+        }
+        // First byte of a 3-byte character, then Enter before the rest arrives
+        assert_eq!(terminal.process_byte(0xE2), None);
+        assert_eq!(terminal.process_byte(0x82), None);
+        let key = terminal.process_byte(b'\r').unwrap();
+        assert_eq!(terminal.handle_key(key), TerminalEvent::CommandReady);
+        assert_eq!(terminal.take_command().unwrap().as_str(), "ab");
+
+        // A stray continuation byte is dropped as well
+        assert_eq!(terminal.process_byte(0xAC), None);
+        assert_eq!(terminal.handle_key(KeyCode::Char(0xAC)), TerminalEvent::None);
+    }
+
+    #[test]
+    fn test_clear_history_through_reader() {
+        let mut history = History::new(crate::history::HistoryConfig::default());
+        history.add("old").unwrap();
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        assert_eq!(reader.history().map(|h| h.len()), Some(1));
+
+        reader.history_mut().unwrap().clear();
+
+        let mut input = MockReader::new(b"\x1b[Ax\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        // Up found nothing to recall
+        assert_eq!(line.as_str(), "x");
+    }
+
+    fn complete_commands(context: &str, candidates: &mut Candidates) {
+        if context.is_empty() {
+            for &word in &["help", "hello", "reboot"] {
+                let _ = candidates.push(word);
+            }
+        }
+    }
+
+    fn completing_config() -> TerminalConfig {
+        TerminalConfig {
+            completion: CompletionConfig {
+                completer: Some(complete_commands),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tab_completes_single_match() {
+        let mut terminal = Terminal::<64>::new(completing_config());
+        terminal.set_buffer("reb").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("reboot "));
+    }
+
+    #[test]
+    fn test_tab_completion_without_room_for_space() {
+        // "reboot" fits exactly, but the space after it doesn't
+        let mut terminal = Terminal::<6>::new(completing_config());
+        terminal.set_buffer("reb").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferFull);
+        assert_eq!(terminal.buffer_str(), Ok("reb"));
+
+        let mut terminal = Terminal::<7>::new(completing_config());
+        terminal.set_buffer("reb").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("reboot "));
+    }
+
+    #[test]
+    fn test_tab_lists_multiple_matches() {
+        let mut terminal = Terminal::<64>::new(completing_config());
+        terminal.set_buffer("he").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::CompletionList);
+        assert_eq!(terminal.completions(), &["help", "hello"]);
+        assert_eq!(terminal.buffer_str(), Ok("hel"));
+
+        terminal.set_buffer("x").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::Bell);
+    }
+
+    fn complete_mixed_case(context: &str, candidates: &mut Candidates) {
+        if context.is_empty() {
+            for &word in &["Help", "HELLO", "reboot"] {
+                let _ = candidates.push(word);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tab_ignore_case_keeps_candidate_case() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            completion: CompletionConfig {
+                completer: Some(complete_mixed_case),
+                ignore_case: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        terminal.set_buffer("he").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::CompletionList);
+        assert_eq!(terminal.completions(), &["Help", "HELLO"]);
+
+        terminal.set_buffer("hel").unwrap();
+        terminal.handle_key(KeyCode::Char(b'p'));
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("Help "));
+    }
+
+    #[test]
+    fn test_read_line_shows_completion_grid() {
+        let config = TerminalConfig {
+            width: Some(80),
+            ..completing_config()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"he\tp\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "help");
+        assert!(output.as_str().contains("> he\r\nhelp   hello\r\n\r> hel"));
+    }
+
+    #[test]
+    fn test_dynamic_prompt_recomputed_on_redraw() {
+        use core::fmt::Write as _;
+
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"ab\r");
+        let mut output = MockWriter::new();
+        let mut draws = 0;
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            let prompt = || {
+                draws += 1;
+                let mut prompt = String::<8>::new();
+                write!(prompt, "{}> ", draws).ok();
+                prompt
+            };
+            block_on(reader.read_line_with_prompt(&mut input, &mut writer, prompt, NO_SIGNAL))
+                .unwrap()
+        };
+        assert_eq!(line.as_str(), "ab");
+        assert_eq!(output.as_str(), "1> \r2> a\r3> ab\r\n");
+    }
+
+    #[test]
+    fn test_feed_without_executor() {
+        let history = History::new(crate::history::HistoryConfig::default());
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        assert_eq!(reader.feed(b'l').unwrap(), ReadStep::Redraw);
+        assert_eq!(reader.feed(b's').unwrap(), ReadStep::Redraw);
+        assert_eq!(reader.terminal().buffer_str(), Ok("ls"));
+        assert_eq!(reader.feed(0x1b).unwrap(), ReadStep::None);
+        assert_eq!(reader.feed(b'[').unwrap(), ReadStep::None);
+        assert_eq!(reader.feed(b'D').unwrap(), ReadStep::MoveCursor);
+
+        let mut command = String::<64>::new();
+        command.push_str("ls").unwrap();
+        assert_eq!(reader.feed(b'\r').unwrap(), ReadStep::Submitted(command));
+        assert_eq!(reader.feed(b'\r').unwrap(), ReadStep::NewLine);
+
+        // Up recalls the submitted command
+        for &byte in b"\x1b[" {
+            reader.feed(byte).unwrap();
+        }
+        assert_eq!(reader.feed(b'A').unwrap(), ReadStep::Redraw);
+        assert_eq!(reader.terminal().buffer_str(), Ok("ls"));
+        assert_eq!(reader.feed(0x03).unwrap(), ReadStep::Interrupted);
+        assert!(matches!(reader.feed(0x04), Err(ReadLineError::EndOfFile)));
+    }
+
+    #[test]
+    fn test_line_offset_preserves_prefix() {
+        let config = TerminalConfig {
+            line_offset: 4,
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"ac\x1b[Db\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(writer.write_str("id: ")).unwrap();
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "abc");
+        assert_eq!(
+            output.as_str(),
+            concat!(
+                "id: > ",
+                // Redraws clear from column 5, leaving the label alone
+                "\x1b[5G\x1b[K> a",
+                "\x1b[5G\x1b[K> ac",
+                "\x1b[8G",
+                "\x1b[5G\x1b[K> abc\x1b[9G",
+                "\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_highlighter_colors_redraw() {
+        fn command_name(line: &str, highlights: &mut crate::highlight::Highlights) {
+            let end = line.find(' ').unwrap_or(line.len());
+            let _ = highlights.push(crate::highlight::Highlight { range: 0..end, color: 2 });
+        }
+        let config = TerminalConfig {
+            highlighter: Some(command_name),
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"l x\x1b[D\x1b[Ds\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "ls x");
+        assert!(output.as_str().ends_with(concat!(
+            "\r\x1b[K> \x1b[32mls\x1b[0m x",
+            // Color codes take no columns, so the cursor lands after `s`
+            "\x1b[5G",
+            "\r\n",
+        )));
+    }
+
+    #[test]
+    fn test_cursor_column_counts_wide_characters() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new("中文\x1b[D\r".as_bytes());
+        let mut output = MockWriter::new();
+        {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        }
+        // Prompt plus one wide character puts the cursor on column 5
+        assert!(output.as_str().ends_with("> 中文\x1b[5G\r\n"));
+    }
+
+    fn vi_reader() -> TerminalReader<64> {
+        let config = TerminalConfig {
+            edit_mode: EditMode::Vi,
+            ..Default::default()
+        };
+        TerminalReader::new(config, None)
+    }
+
+    fn feed_all(reader: &mut TerminalReader<64>, bytes: &[u8]) {
+        for &byte in bytes {
+            reader.feed(byte).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_vi_normal_mode_motions() {
+        let mut reader = vi_reader();
+        feed_all(&mut reader, b"hello world\x1b0");
+        assert!(reader.terminal().is_vi_normal());
+        assert_eq!(reader.terminal().cursor_position(), 0);
+        // `l` moves, `x` deletes, `a` appends after the cursor
+        feed_all(&mut reader, b"lxaE");
+        assert!(!reader.terminal().is_vi_normal());
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "hlElo world");
+        feed_all(&mut reader, b"\x1b$iX");
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "hlElo worldX");
+    }
+
+    #[test]
+    fn test_vi_macro_records_escape() {
+        let mut reader = vi_reader();
+        reader.terminal_mut().start_recording();
+        feed_all(&mut reader, b"abc\x1b0x");
+        let keys = reader.terminal_mut().stop_recording().unwrap();
+        assert_eq!(keys[3], KeyCode::Escape);
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "bc");
+
+        // Replaying from insert mode switches to normal mode the same way
+        let mut terminal = Terminal::<64>::new(vi_reader().terminal().config);
+        terminal.replay(&keys);
+        assert!(terminal.is_vi_normal());
+        assert_eq!(terminal.buffer_str(), Ok("bc"));
+    }
+
+    #[test]
+    fn test_vi_delete_commands() {
+        let mut reader = vi_reader();
+        feed_all(&mut reader, b"one two three\x1b0dw");
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "two three");
+        // A `d` followed by another key does nothing
+        feed_all(&mut reader, b"dhx");
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "wo three");
+        feed_all(&mut reader, b"dd");
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "");
+    }
+
+    #[test]
+    fn test_vi_arrow_keys_stay_in_insert_mode() {
+        let mut reader = vi_reader();
+        feed_all(&mut reader, b"ac\x1b[Db");
+        assert!(!reader.terminal().is_vi_normal());
+        assert_eq!(reader.terminal().buffer_str().unwrap(), "abc");
+        // Enter submits from normal mode and the next line starts inserting
+        feed_all(&mut reader, b"\x1bh");
+        assert_eq!(reader.feed(b'\r').unwrap(), ReadStep::Submitted("abc".try_into().unwrap()));
+        assert!(!reader.terminal().is_vi_normal());
+    }
+
+    /// Reader whose link is broken
+    struct FailingReader;
+
+    impl embedded_io_async::ErrorType for FailingReader {
+        type Error = embedded_io_async::ErrorKind;
+    }
+
+    impl Read for FailingReader {
+        async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+            Err(embedded_io_async::ErrorKind::BrokenPipe)
+        }
+    }
+
+    #[test]
+    fn test_read_errors_end_the_read() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+
+        let result = block_on(reader.read_line(&mut FailingReader, &mut writer, NO_SIGNAL));
+        assert!(matches!(result, Err(ReadLineError::IoError)));
+
+        // The input ends before the line does
+        let mut input = MockReader::new(b"ab");
+        let result = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL));
+        assert!(matches!(result, Err(ReadLineError::EndOfFile)));
+    }
+
+    /// Reader that lets `delay` polls pass before handing out the first byte
+    struct SlowReader<'a> {
+        inner: MockReader<'a>,
+        delay: usize,
+    }
+
+    impl embedded_io_async::ErrorType for SlowReader<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for SlowReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            while self.delay > 0 {
+                self.delay -= 1;
+                embassy_futures::yield_now().await;
+            }
+            self.inner.read(buf).await
+        }
+    }
+
+    #[test]
+    fn test_idle_callback_redraws_prompt() {
+        use core::cell::Cell;
+        use core::fmt::Write as _;
+
+        let ticks = Cell::new(0);
+        let prompt = || {
+            let mut prompt = String::<8>::new();
+            let _ = write!(prompt, "{}> ", ticks.get());
+            prompt
+        };
+        let on_idle = || {
+            ticks.set(ticks.get() + 1);
+            true
+        };
+        // The first read outlasts one idle tick
+        let mut input = SlowReader { inner: MockReader::new(b"a\r"), delay: 3 };
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            let read = reader.read_line_with_idle(
+                &mut input,
+                &mut writer,
+                prompt,
+                NO_SIGNAL,
+                embassy_futures::yield_now,
+                on_idle,
+            );
+            block_on(read).unwrap()
+        };
+        assert_eq!(line.as_str(), "a");
+        assert_eq!(ticks.get(), 1);
+        assert_eq!(output.as_str(), "0> \r\x1b[K1> \r\x1b[K1> a\r\n");
+    }
+
+    /// Reader that hands out `left` bytes, then waits forever
+    struct StallingReader<'a> {
+        inner: MockReader<'a>,
+        left: usize,
+    }
+
+    impl embedded_io_async::ErrorType for StallingReader<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for StallingReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.left == 0 {
+                core::future::pending::<()>().await;
+            }
+            self.left -= 1;
+            self.inner.read(buf).await
+        }
+    }
+
+    #[test]
+    fn test_timeout_keeps_typed_text() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, true);
+
+        let mut input = StallingReader { inner: MockReader::new(b"ab"), left: 2 };
+        let timeout = async {
+            for _ in 0..8 {
+                embassy_futures::yield_now().await;
+            }
+        };
+        let result = reader.read_line_with_timeout(&mut input, &mut writer, NO_SIGNAL, timeout);
+        assert!(matches!(block_on(result), Err(ReadLineError::Timeout)));
+
+        let mut input = StallingReader { inner: MockReader::new(b"c\r"), left: 2 };
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "abc");
+        // The kept text is drawn again after the prompt
+        assert!(output.as_str().ends_with("> ab\r\x1b[K> ab\r\x1b[K> abc\r\n"));
+    }
+
+    /// Reader that fires a resize signal before handing out byte `at`
+    struct ResizingReader<'a> {
+        inner: MockReader<'a>,
+        read: usize,
+        at: usize,
+        size: (u16, u16),
+        signal: &'a Signal<NoopRawMutex, (u16, u16)>,
+    }
+
+    impl embedded_io_async::ErrorType for ResizingReader<'_> {
+        type Error = core::convert::Infallible;
+    }
+
+    impl Read for ResizingReader<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            if self.read == self.at {
+                self.at = usize::MAX;
+                self.signal.signal(self.size);
+                // Give the signal a chance to win the select
+                embassy_futures::yield_now().await;
+            }
+            self.read += 1;
+            self.inner.read(buf).await
+        }
+    }
+
+    #[test]
+    fn test_resize_signal_redraws_mid_edit() {
+        let resize = Signal::<NoopRawMutex, (u16, u16)>::new();
+        let mut input = ResizingReader {
+            inner: MockReader::new(b"ab\r"),
+            read: 0,
+            at: 1,
+            size: (40, 24),
+            signal: &resize,
+        };
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            let read = reader.read_line_with_resize(&mut input, &mut writer, NO_SIGNAL, Some(&resize));
+            block_on(read).unwrap()
+        };
+        assert_eq!(line.as_str(), "ab");
+        assert_eq!(reader.terminal().width(), Some(40));
+        assert_eq!(reader.terminal().height(), Some(24));
+        // The line is drawn again between the two keystrokes
+        assert_eq!(output.as_str(), "> \r> a\r> a\r> ab\r\n");
+    }
+
+    #[test]
+    fn test_resize_rewraps_long_line() {
+        let resize = Signal::<NoopRawMutex, (u16, u16)>::new();
+        let mut input = ResizingReader {
+            inner: MockReader::new(b"abcdefghijk\r"),
+            read: 0,
+            at: 11,
+            size: (10, 24),
+            signal: &resize,
+        };
+        let config = TerminalConfig {
+            width: Some(80),
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut output = MockWriter::new();
+        {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            let read = reader.read_line_with_resize(&mut input, &mut writer, NO_SIGNAL, Some(&resize));
+            block_on(read).unwrap();
+        }
+        // At 10 columns the 13 characters now span two rows, so the redraw
+        // first returns to the row holding the prompt
+        let redraw = "> abcdefghijk\x1b[1A\r\x1b[K\x1b[0J> abcdefghijk";
+        assert!(output.as_str().contains(redraw));
+    }
+
+    #[test]
+    fn test_batched_input_keeps_bytes_after_submit() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::chunked(b"ab\rcd\r", 8);
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let first = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        let second = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(first.as_str(), "ab");
+        assert_eq!(second.as_str(), "cd");
+    }
+
+    #[test]
+    fn test_read_key() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::chunked("\x1b[Aé\x03ls\r".as_bytes(), 4);
+        assert_eq!(block_on(reader.read_key(&mut input)).unwrap(), KeyCode::ArrowUp);
+        assert_eq!(block_on(reader.read_key(&mut input)).unwrap(), KeyCode::Unicode('é'));
+        assert_eq!(block_on(reader.read_key(&mut input)).unwrap(), KeyCode::CtrlC);
+        assert_eq!(reader.terminal().buffer_str(), Ok(""));
+
+        // The rest of the batch is still read as a line
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "ls");
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"xN\r");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        assert!(!block_on(reader.confirm(&mut input, &mut writer, "Erase?", true)).unwrap());
+        // Enter picks the default
+        assert!(block_on(reader.confirm(&mut input, &mut writer, "Erase?", true)).unwrap());
+        assert_eq!(output.as_str(), "Erase? [Y/n] n\r\nErase? [Y/n] y\r\n");
+    }
+
+    #[test]
+    fn test_select_rewrites_changed_items() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"\x1b[B\x1b[B\x1b[B\r");
+        let mut output = MockWriter::new();
+        let choice = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(reader.select(&mut input, &mut writer, &["a", "b", "c"], 0)).unwrap()
+        };
+        assert_eq!(choice, 2);
+        assert_eq!(
+            output.as_str(),
+            concat!(
+                "\r\x1b[7ma\x1b[27m\r\n\rb\r\n\rc\r\n",
+                // Down: unmark `a`, mark `b`, return below the list
+                "\x1b[3A\ra\x1b[1B\r\x1b[7mb\x1b[27m\x1b[2B\r",
+                "\x1b[2A\rb\x1b[1B\r\x1b[7mc\x1b[27m\x1b[1B\r",
+            )
+        );
+    }
+
+    #[test]
+    fn test_select_plain_and_interrupt() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"\x1b[A\x03");
+        let mut output = MockWriter::new();
+        let result = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.select(&mut input, &mut writer, &["a", "b"], 5))
+        };
+        assert!(matches!(result, Err(ReadLineError::Interrupted)));
+        assert_eq!(output.as_str(), "\r  a\r\n\r> b\r\n\r\n\r> a\r\n\r  b\r\n");
+    }
+
+    #[test]
+    fn test_read_number_reprompts() {
+        let history = History::new(crate::history::HistoryConfig::default());
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        let mut input = MockReader::chunked(b"abc\r300\r42\r", 4);
+        let mut output = MockWriter::new();
+        let number = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            let read = reader.read_number_in(&mut input, &mut writer, "n: ", 1u16..=100);
+            block_on(read).unwrap()
+        };
+        assert_eq!(number, 42);
+        assert!(reader.history().unwrap().is_empty());
+        assert_eq!(
+            output.as_str(),
+            concat!(
+                "n: \rn: a\rn: ab\rn: abc\r\nInvalid number\r\n",
+                "n: \rn: 3\rn: 30\rn: 300\r\nOut of range\r\n",
+                "n: \rn: 4\rn: 42\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_read_number_interrupted() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"1\x03");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let result = block_on(reader.read_number::<u32, _, _>(&mut input, &mut writer, "n: "));
+        assert!(matches!(result, Err(ReadLineError::Interrupted)));
+        // Later reads handle Ctrl+C as usual again
+        let mut input = MockReader::new(b"a\x03b\r");
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "b");
+    }
+
+    #[test]
+    fn test_read_number_dropped_restores_reader() {
+        let history = History::new(crate::history::HistoryConfig::default());
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        let mut input = MockReader::stalling(b"12");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        {
+            // Give up on the prompt while it waits for more input
+            let read = reader.read_number::<u32, _, _>(&mut input, &mut writer, "n: ");
+            let raced = block_on(embassy_futures::select::select(read, async {}));
+            assert!(matches!(raced, embassy_futures::select::Either::Second(())));
+        }
+        assert!(reader.history().is_some());
+        assert!(!reader.interruptible);
+    }
+
+    fn no_spaces(line: &str) -> Result<(), &'static str> {
+        if line.contains(' ') {
+            Err("no spaces allowed")
+        } else {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_validator_keeps_rejected_line() {
+        let config = TerminalConfig {
+            ansi_enabled: false,
+            validator: Some(no_spaces),
+            ..Default::default()
+        };
+        let history = History::new(crate::history::HistoryConfig::default());
+        let mut reader = TerminalReader::<64>::new(config, Some(history));
+        let mut input = MockReader::new(b"a b\r\x08\x08\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "a");
+        assert_eq!(reader.history().unwrap().len(), 1);
+        assert_eq!(
+            output.as_str(),
+            concat!(
+                "> \r> a\r> a \r> a b\r\nno spaces allowed\r\n",
+                "\r> a b\r> a \r> a\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_feed_reports_rejection() {
+        let config = TerminalConfig {
+            validator: Some(no_spaces),
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        for &byte in b"a b" {
+            reader.feed(byte).unwrap();
+        }
+        assert_eq!(reader.feed(b'\r').unwrap(), ReadStep::Rejected("no spaces allowed"));
+        assert_eq!(reader.terminal().buffer_str(), Ok("a b"));
+    }
+
+    #[test]
+    fn test_ctrl_d_deletes_on_non_empty_line() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        for &byte in b"ab\x1b[D" {
+            if let Some(key) = terminal.process_byte(byte) {
+                terminal.handle_key(key);
+            }
+        }
+        assert_eq!(terminal.handle_key(KeyCode::CtrlD), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("a"));
+        // At the end of the line there is nothing to delete
+        assert_eq!(terminal.handle_key(KeyCode::CtrlD), TerminalEvent::None);
+        terminal.clear_buffer();
+        assert_eq!(terminal.handle_key(KeyCode::CtrlD), TerminalEvent::EndOfFile);
+    }
+
+    #[test]
+    fn test_ctrl_d_always_eof() {
+        let config = TerminalConfig {
+            ctrl_d_always_eof: true,
+            ..Default::default()
+        };
+        let mut terminal = Terminal::<64>::new(config);
+        terminal.set_buffer("ab").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::CtrlD), TerminalEvent::EndOfFile);
+    }
+
+    #[test]
+    fn test_read_line_prompt_override() {
+        let config = TerminalConfig {
+            ansi_enabled: false,
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"y\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line_prompt(&mut input, &mut writer, "sure? ", NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "y");
+        assert_eq!(output.as_str(), "sure? \rsure? y\r\n");
+        assert_eq!(reader.terminal().config.prompt, "> ");
+    }
+
+    #[test]
+    fn test_crlf_submits_once() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut submitted: Vec<String<64>, 4> = Vec::new();
+        for &byte in b"ab\r\ncd\ne\r" {
+            match reader.feed(byte).unwrap() {
+                ReadStep::Submitted(line) => submitted.push(line).unwrap(),
+                ReadStep::NewLine => panic!("empty line submitted"),
+                _ => {}
+            }
+        }
+        assert_eq!(submitted, ["ab", "cd", "e"]);
+    }
+
+    #[test]
+    fn test_newline_modes_ignore_other_byte() {
+        let modes = [(NewlineMode::Cr, b'\r', b'\n'), (NewlineMode::Lf, b'\n', b'\r')];
+        for (newline, enter, ignored) in modes {
+            let config = TerminalConfig { newline, ..Default::default() };
+            let mut terminal = Terminal::<64>::new(config);
+            assert_eq!(terminal.process_byte(ignored), None);
+            assert_eq!(terminal.process_byte(enter), Some(KeyCode::Enter));
+            assert_eq!(terminal.process_byte(enter), Some(KeyCode::Enter));
+        }
+    }
+
+    #[test]
+    fn test_multibyte_reads_split_sequences() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        // Reads of 3 bytes split the escape sequence and the UTF-8 character
+        let mut input = MockReader::chunked("ac\x1b[Dbé\r".as_bytes(), 3);
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "abéc");
+    }
+
+    #[test]
+    fn test_continuation_lines_are_joined() {
+        let config = TerminalConfig {
+            prompt: "$ ",
+            ..Default::default()
+        };
+        let history = History::new(crate::history::HistoryConfig::default());
+        let mut reader = TerminalReader::<64>::new(config, Some(history));
+        let mut input = MockReader::new(b"echo a \\\rb\\\\\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "echo a b\\\\");
+        assert!(output.as_str().contains("$ echo a \\\r\n> \r> b"));
+        assert!(!reader.is_continuing());
+        assert!(reader.history().unwrap().iter().eq(["echo a b\\\\"]));
+    }
+
+    #[test]
+    fn test_continuation_ends_on_empty_line_and_aborts_on_ctrl_c() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        let mut input = MockReader::new(b"a\\\rb\\\r\x03c\\\r\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        // Ctrl+C dropped "a" and "b", the empty line submits "c"
+        assert_eq!(line.as_str(), "c");
+    }
+
+    #[test]
+    fn test_continuation_dropped_at_end_of_file() {
+        let config = TerminalConfig {
+            prompt: "$ ",
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"a\\\r");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let result = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL));
+        assert!(matches!(result, Err(ReadLineError::EndOfFile)));
+        assert!(!reader.is_continuing());
+
+        // The next read starts a fresh command with the normal prompt
+        let mut input = MockReader::new(b"b\r");
+        let mut output = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut output, false);
+        let line = block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        assert_eq!(line.as_str(), "b");
+        assert_eq!(output.as_str(), "$ \r$ b\r\n");
+    }
+
+    #[test]
+    fn test_redraw_wraps_long_lines() {
+        let config = TerminalConfig {
+            width: Some(10),
+            ..Default::default()
+        };
+        let mut reader = TerminalReader::<64>::new(config, None);
+        let mut input = MockReader::new(b"abcdefghi\x1b[D\x1b[D\r");
+        let mut output = MockWriter::new();
+        let line = {
+            let mut writer = TerminalWriter::new(&mut output, true);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap()
+        };
+        assert_eq!(line.as_str(), "abcdefghi");
+        let output = output.as_str();
+        // Filling the first row exactly moves the cursor to the next one
+        assert!(output.contains("\r\x1b[K\x1b[0J> abcdefgh\r\n"));
+        // The next redraw starts from the prompt's row
+        assert!(output.contains("\x1b[1A\r\x1b[K\x1b[0J> abcdefghi"));
+        // Moving left across the row boundary goes up a row
+        assert!(output.ends_with("> abcdefghi\x1b[1G\x1b[1A\x1b[10G\x1b[1B\r\n"));
+    }
+
+    #[test]
+    fn test_history_prefix_search_restores_line() {
+        let mut history = History::new(crate::history::HistoryConfig::default());
+        for command in ["git status", "ls", "git log"] {
+            history.add(command).unwrap();
+        }
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        for &byte in b"git " {
+            reader.feed(byte).unwrap();
+        }
+        let up = |reader: &mut TerminalReader<64>| {
+            for &byte in b"\x1b[A" {
+                reader.feed(byte).unwrap();
+            }
+        };
+        let down = |reader: &mut TerminalReader<64>| {
+            for &byte in b"\x1b[B" {
+                reader.feed(byte).unwrap();
+            }
+        };
+        up(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("git log"));
+        up(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("git status"));
+        // No older match keeps the current entry
+        up(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("git status"));
+
+        down(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("git log"));
+        down(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("git "));
+
+        // An empty line walks through every entry
+        reader.feed(0x03).unwrap();
+        up(&mut reader);
+        up(&mut reader);
+        assert_eq!(reader.terminal().buffer_str(), Ok("ls"));
+    }
+
+    #[test]
+    fn test_history_down_restores_draft() {
+        let mut history = History::new(crate::history::HistoryConfig::default());
+        for command in ["ls", "lsblk"] {
+            history.add(command).unwrap();
+        }
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), Some(history));
+        for &byte in b"lsx\x1b[D" {
+            reader.feed(byte).unwrap();
+        }
+        // Down before browsing keeps the draft
+        for &byte in b"\x1b[B" {
+            assert_eq!(reader.feed(byte).unwrap(), ReadStep::None);
+        }
+        assert_eq!(reader.terminal().buffer_str(), Ok("lsx"));
+
+        for &byte in b"\x1b[A\x1b[A\x1b[B\x1b[B" {
+            reader.feed(byte).unwrap();
+        }
+        assert_eq!(reader.terminal().buffer_str(), Ok("lsx"));
+        assert_eq!(reader.terminal().cursor_position(), 2);
+    }
+
+    #[test]
+    fn test_queued_output_follows_submit() {
+        let mut reader = TerminalReader::<64>::new(TerminalConfig::default(), None);
+        reader.queue_output("ack ").unwrap();
+        reader.queue_output("ok\r\n").unwrap();
+
+        let mut input = MockReader::new(b"go\r");
+        let mut output = MockWriter::new();
+        {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        }
+        assert!(output.as_str().ends_with("> go\r\nack ok\r\n"));
+
+        // The queue is emptied once written
+        let mut input = MockReader::new(b"go\r");
+        let mut output = MockWriter::new();
+        {
+            let mut writer = TerminalWriter::new(&mut output, false);
+            block_on(reader.read_line(&mut input, &mut writer, NO_SIGNAL)).unwrap();
+        }
+        assert!(output.as_str().ends_with("> go\r\n"));
+    }
+
+    static COMPLETER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn counting_completer(context: &str, candidates: &mut Candidates) {
+        COMPLETER_CALLS.fetch_add(1, Ordering::SeqCst);
+        complete_commands(context, candidates);
+    }
+
+    #[test]
+    fn test_tab_cycling_reuses_cached_candidates() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            completion: CompletionConfig {
+                completer: Some(counting_completer),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        terminal.set_buffer("he").unwrap();
+
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::CompletionList);
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("help"));
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("hello"));
+        assert_eq!(COMPLETER_CALLS.load(Ordering::SeqCst), 1);
+
+        // Editing the buffer invalidates the cache
+        terminal.handle_key(KeyCode::Backspace);
+        terminal.handle_key(KeyCode::Tab);
+        assert_eq!(COMPLETER_CALLS.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_tab_extends_common_prefix() {
+        let mut terminal = Terminal::<64>::new(completing_config());
+        terminal.set_buffer("h").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Tab), TerminalEvent::CompletionList);
+        assert_eq!(terminal.buffer_str(), Ok("hel"));
+    }
+
+    #[test]
+    fn test_overwrite_mode() {
+        let mut terminal = Terminal::<8>::new(TerminalConfig::default());
+        terminal.set_buffer("abcd").unwrap();
+        terminal.handle_key(KeyCode::Home);
+        terminal.handle_key(KeyCode::ArrowRight);
+        assert_eq!(feed(&mut terminal, b"\x1b[2~"), Some(KeyCode::Insert));
+        assert_eq!(terminal.handle_key(KeyCode::Insert), TerminalEvent::None);
+        assert!(terminal.is_overwrite());
+
+        for &b in b"XYZW" {
+            terminal.handle_key(KeyCode::Char(b));
+        }
+        // Overwrites "bcd", then grows at the end
+        assert_eq!(terminal.buffer_str(), Ok("aXYZW"));
+        terminal.handle_key(KeyCode::Home);
+        terminal.handle_key(KeyCode::Unicode('é'));
+        assert_eq!(terminal.buffer_str(), Ok("éXYZW"));
+        terminal.handle_key(KeyCode::Char(b'e'));
+        assert_eq!(terminal.buffer_str(), Ok("éeYZW"));
+
+        // Backspace still deletes
+        terminal.handle_key(KeyCode::Backspace);
+        assert_eq!(terminal.buffer_str(), Ok("éYZW"));
+
+        terminal.set_overwrite(false);
+        terminal.handle_key(KeyCode::Char(b'x'));
+        assert_eq!(terminal.buffer_str(), Ok("éxYZW"));
+    }
+
+    #[test]
+    fn test_custom_key_bindings() {
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bind(0x03, None);
+        key_bindings.bind(0x07, Some(KeyCode::Custom(1)));
+        let mut terminal = Terminal::<64>::new(TerminalConfig {
+            key_bindings,
+            ..Default::default()
+        });
+        // Ctrl+C no longer interrupts, Ctrl+G triggers the custom action
+        assert_eq!(terminal.process_byte(0x03), None);
+        let key = terminal.process_byte(0x07).unwrap();
+        assert_eq!(terminal.handle_key(key), TerminalEvent::Custom(1));
+        assert_eq!(terminal.process_byte(0x01), Some(KeyCode::Ctrl(b'a')));
+        assert_eq!(terminal.handle_key(KeyCode::Ctrl(b'a')), TerminalEvent::Ctrl(b'a'));
+        assert_eq!(terminal.process_byte(b'\r'), Some(KeyCode::Enter));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        for &b in b"ab cd" {
+            terminal.handle_key(KeyCode::Char(b));
+        }
+        terminal.handle_key(KeyCode::Backspace);
+        terminal.handle_key(KeyCode::Backspace);
+        assert_eq!(terminal.buffer_str(), Ok("ab "));
+
+        // Each deletion is its own step, the typing run is a single one
+        assert_eq!(feed(&mut terminal, b"\x1f"), Some(KeyCode::Undo));
+        assert_eq!(terminal.handle_key(KeyCode::Undo), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("ab c"));
+        terminal.undo();
+        assert_eq!(terminal.buffer_str(), Ok("ab cd"));
+        assert_eq!(terminal.cursor_byte_offset(), 5);
+        terminal.undo();
+        assert_eq!(terminal.buffer_str(), Ok(""));
+        assert_eq!(terminal.undo(), TerminalEvent::None);
+
+        assert_eq!(feed(&mut terminal, b"\x1e"), Some(KeyCode::Redo));
+        assert_eq!(terminal.handle_key(KeyCode::Redo), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("ab cd"));
+
+        // A new edit drops the redo steps
+        terminal.handle_key(KeyCode::Char(b'!'));
+        assert_eq!(terminal.redo(), TerminalEvent::None);
+        terminal.undo();
+        assert_eq!(terminal.buffer_str(), Ok("ab cd"));
+    }
+
+    #[test]
+    fn test_undo_depth_is_bounded() {
+        let mut terminal = Terminal::<64, 2>::new(TerminalConfig::default());
+        terminal.set_buffer("abc").unwrap();
+        for _ in 0..3 {
+            terminal.handle_key(KeyCode::Backspace);
+        }
+        terminal.undo();
+        terminal.undo();
+        assert_eq!(terminal.buffer_str(), Ok("ab"));
+        assert_eq!(terminal.undo(), TerminalEvent::None);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("a").unwrap();
+        assert_eq!(terminal.handle_key(KeyCode::Transpose), TerminalEvent::None);
+
+        // At the end the last two characters swap
+        terminal.set_buffer("abé").unwrap();
+        assert_eq!(feed(&mut terminal, b"\x14"), Some(KeyCode::Transpose));
+        assert_eq!(terminal.handle_key(KeyCode::Transpose), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("aéb"));
+        assert_eq!(terminal.cursor_position(), 3);
+
+        // In the middle the cursor advances past the swapped pair
+        terminal.handle_key(KeyCode::Home);
+        terminal.handle_key(KeyCode::ArrowRight);
+        terminal.handle_key(KeyCode::Transpose);
+        assert_eq!(terminal.buffer_str(), Ok("éab"));
+        assert_eq!(terminal.cursor_position(), 2);
+
+        terminal.handle_key(KeyCode::Home);
+        assert_eq!(terminal.handle_key(KeyCode::Transpose), TerminalEvent::None);
+    }
+
+    #[test]
+    fn test_kill_and_yank() {
+        let mut terminal = Terminal::<64>::new(TerminalConfig::default());
+        terminal.set_buffer("send peer  hello").unwrap();
+        assert_eq!(feed(&mut terminal, b"\x17"), Some(KeyCode::KillWord));
+        assert_eq!(terminal.handle_key(KeyCode::KillWord), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("send peer  "));
+        terminal.handle_key(KeyCode::KillWord);
+        assert_eq!(terminal.buffer_str(), Ok("send "));
+
+        // Yank pastes the most recent kill at the cursor
+        terminal.handle_key(KeyCode::Home);
+        assert_eq!(feed(&mut terminal, b"\x19"), Some(KeyCode::Yank));
+        assert_eq!(terminal.handle_key(KeyCode::Yank), TerminalEvent::BufferChanged);
+        assert_eq!(terminal.buffer_str(), Ok("peer  send "));
+        assert_eq!(terminal.cursor_position(), 6);
+
+        terminal.handle_key(KeyCode::KillToEnd);
+        assert_eq!(terminal.buffer_str(), Ok("peer  "));
+        terminal.handle_key(KeyCode::KillToStart);
+        assert_eq!(terminal.buffer_str(), Ok(""));
+        terminal.handle_key(KeyCode::Yank);
+        assert_eq!(terminal.buffer_str(), Ok("peer  "));
+
+        // Undo brings back killed text
+        terminal.undo();
+        terminal.undo();
+        assert_eq!(terminal.buffer_str(), Ok("peer  "));
+        terminal.handle_key(KeyCode::Home);
+        assert_eq!(terminal.handle_key(KeyCode::KillToStart), TerminalEvent::None);
+    }
+}
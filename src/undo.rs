@@ -0,0 +1,92 @@
+use heapless::{Deque, Vec};
+
+/// Line buffer contents and cursor saved for undo
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Snapshot<const BUF_SIZE: usize> {
+    pub buffer: Vec<u8, BUF_SIZE>,
+    pub cursor: usize,
+}
+
+/// Bounded undo and redo history of line buffer states
+///
+/// Holds at most `DEPTH` undo steps; recording beyond that forgets the
+/// oldest one. Recording a new edit discards everything that could be redone.
+pub(crate) struct UndoStack<const BUF_SIZE: usize, const DEPTH: usize> {
+    undo: Deque<Snapshot<BUF_SIZE>, DEPTH>,
+    redo: Vec<Snapshot<BUF_SIZE>, DEPTH>,
+}
+
+impl<const BUF_SIZE: usize, const DEPTH: usize> UndoStack<BUF_SIZE, DEPTH> {
+    pub fn new() -> Self {
+        Self {
+            undo: Deque::new(),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Save the state before an edit
+    pub fn record(&mut self, before: Snapshot<BUF_SIZE>) {
+        self.redo.clear();
+        self.push_undo(before);
+    }
+
+    /// Step back, returning the state to restore
+    pub fn undo(&mut self, current: Snapshot<BUF_SIZE>) -> Option<Snapshot<BUF_SIZE>> {
+        let previous = self.undo.pop_back()?;
+        let _ = self.redo.push(current);
+        Some(previous)
+    }
+
+    /// Step forward again after an undo, returning the state to restore
+    pub fn redo(&mut self, current: Snapshot<BUF_SIZE>) -> Option<Snapshot<BUF_SIZE>> {
+        let next = self.redo.pop()?;
+        self.push_undo(current);
+        Some(next)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push_undo(&mut self, snapshot: Snapshot<BUF_SIZE>) {
+        if self.undo.is_full() {
+            self.undo.pop_front();
+        }
+        let _ = self.undo.push_back(snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(text: &str) -> Snapshot<16> {
+        Snapshot {
+            buffer: Vec::from_slice(text.as_bytes()).unwrap(),
+            cursor: text.len(),
+        }
+    }
+
+    #[test]
+    fn test_depth_forgets_oldest() {
+        let mut stack = UndoStack::<16, 2>::new();
+        stack.record(snapshot("a"));
+        stack.record(snapshot("ab"));
+        stack.record(snapshot("abc"));
+        assert_eq!(stack.undo(snapshot("abcd")), Some(snapshot("abc")));
+        assert_eq!(stack.undo(snapshot("abc")), Some(snapshot("ab")));
+        assert_eq!(stack.undo(snapshot("ab")), None);
+    }
+
+    #[test]
+    fn test_record_clears_redo() {
+        let mut stack = UndoStack::<16, 4>::new();
+        stack.record(snapshot("a"));
+        assert_eq!(stack.undo(snapshot("ab")), Some(snapshot("a")));
+        assert_eq!(stack.redo(snapshot("a")), Some(snapshot("ab")));
+        assert_eq!(stack.undo(snapshot("ab")), Some(snapshot("a")));
+        stack.record(snapshot("a"));
+        assert_eq!(stack.redo(snapshot("ax")), None);
+    }
+}
@@ -0,0 +1,77 @@
+//! Display width of characters on a terminal.
+//!
+//! A small built-in table covers the common cases: East Asian wide and
+//! fullwidth characters plus most emoji take two columns, combining marks
+//! and other zero-width characters take none. Everything else counts as one
+//! column.
+
+/// Ranges of characters drawn two columns wide
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),   // Hangul Jamo initials
+    (0x2E80, 0x303E),   // CJK radicals, punctuation
+    (0x3041, 0x33FF),   // Kana, CJK compatibility
+    (0x3400, 0x4DBF),   // CJK extension A
+    (0x4E00, 0x9FFF),   // CJK unified ideographs
+    (0xA000, 0xA4CF),   // Yi
+    (0xAC00, 0xD7A3),   // Hangul syllables
+    (0xF900, 0xFAFF),   // CJK compatibility ideographs
+    (0xFE30, 0xFE4F),   // CJK compatibility forms
+    (0xFF00, 0xFF60),   // Fullwidth forms
+    (0xFFE0, 0xFFE6),   // Fullwidth signs
+    (0x1F300, 0x1F64F), // Pictographs, emoticons
+    (0x1F900, 0x1F9FF), // Supplemental pictographs
+    (0x20000, 0x3FFFD), // CJK extensions B and later
+];
+
+/// Ranges of characters that take no column
+const ZERO: &[(u32, u32)] = &[
+    (0x0300, 0x036F), // Combining diacritical marks
+    (0x1AB0, 0x1AFF), // Combining diacritical marks extended
+    (0x1DC0, 0x1DFF), // Combining diacritical marks supplement
+    (0x200B, 0x200F), // Zero-width space, joiners, direction marks
+    (0x20D0, 0x20FF), // Combining marks for symbols
+    (0xFE00, 0xFE0F), // Variation selectors
+    (0xFE20, 0xFE2F), // Combining half marks
+];
+
+/// Get the number of columns a character occupies
+pub fn char_width(c: char) -> usize {
+    let code = c as u32;
+    let within = |ranges: &[(u32, u32)]| ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&code));
+    if code < 0x300 {
+        1
+    } else if within(ZERO) {
+        0
+    } else if within(WIDE) {
+        2
+    } else {
+        1
+    }
+}
+
+/// Get the number of columns a string occupies
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_width() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('中'), 2);
+        assert_eq!(char_width('\u{0301}'), 0);
+        assert_eq!(char_width('😀'), 2);
+    }
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("abc"), 3);
+        assert_eq!(display_width("日本語"), 6);
+        // `e` followed by a combining acute accent
+        assert_eq!(display_width("cafe\u{0301}"), 4);
+    }
+}
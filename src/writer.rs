@@ -1,10 +1,61 @@
 use core::fmt;
 use embedded_io_async::Write as AsyncWrite;
 
+use crate::completion::{self, CompletionLayout};
+use crate::width::{char_width, display_width};
+
+/// Number of cells in a bar drawn by [`TerminalWriter::write_progress`]
+pub const PROGRESS_WIDTH: usize = 20;
+
+/// Maximum number of columns drawn by [`TerminalWriter::write_table`]
+pub const MAX_TABLE_COLUMNS: usize = 8;
+
+/// Maximum number of pieces queued in a [`Segments`] builder
+pub const MAX_SEGMENTS: usize = 16;
+
 /// Terminal writer for formatted output with ANSI support
 pub struct TerminalWriter<'a, W: AsyncWrite> {
     writer: &'a mut W,
     ansi_enabled: bool,
+    /// Bytes written since creation or the last reset
+    written: usize,
+    /// Width to wrap [`write_str`](Self::write_str) output at
+    wrap_width: Option<u16>,
+    /// Current column while wrapping
+    column: usize,
+    /// Escape sequence being written while wrapping, which may span writes
+    escape: Escape,
+    /// Flush the inner writer after every write
+    auto_flush: bool,
+    /// Colors of the message helpers
+    theme: Theme,
+}
+
+/// Colors used by [`TerminalWriter::write_error`] and the other message
+/// helpers
+///
+/// `None` writes that kind of message without color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Color of [`TerminalWriter::write_error`]
+    pub error: Option<u8>,
+    /// Color of [`TerminalWriter::write_success`]
+    pub success: Option<u8>,
+    /// Color of [`TerminalWriter::write_warning`]
+    pub warning: Option<u8>,
+    /// Color of [`TerminalWriter::write_info`]
+    pub info: Option<u8>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            error: Some(colors::RED),
+            success: Some(colors::GREEN),
+            warning: Some(colors::YELLOW),
+            info: Some(colors::CYAN),
+        }
+    }
 }
 
 impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
@@ -13,25 +64,144 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         Self {
             writer,
             ansi_enabled,
+            written: 0,
+            wrap_width: None,
+            column: 0,
+            escape: Escape::None,
+            auto_flush: true,
+            theme: Theme::default(),
         }
     }
 
+    /// Break lines of [`write_str`](Self::write_str) output at `width` columns
+    ///
+    /// For terminals that do not wrap on their own. The writer tracks the
+    /// column it has reached and writes `\r\n` before a character that would
+    /// not fit; `\r` and `\n` in the output restart the count and escape
+    /// sequences take no columns. [`write_bytes`](Self::write_bytes) is never
+    /// wrapped.
+    pub fn with_width(mut self, width: u16) -> Self {
+        self.wrap_width = Some(width);
+        self
+    }
+
+    /// Choose whether every write is followed by a flush of the inner writer
+    ///
+    /// On by default. Turn it off together with a
+    /// [`BufferedWriter`](crate::BufferedWriter) to send output in bursts;
+    /// it is then only flushed by [`flush`](Self::flush) and by
+    /// [`TerminalReader`](crate::terminal::TerminalReader) before it waits
+    /// for input.
+    pub fn with_auto_flush(mut self, enabled: bool) -> Self {
+        self.auto_flush = enabled;
+        self
+    }
+
+    /// Use `theme` for the message helpers such as
+    /// [`write_error`](Self::write_error)
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Get the colors used by the message helpers
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Check whether ANSI escape codes are enabled
+    pub fn ansi_enabled(&self) -> bool {
+        self.ansi_enabled
+    }
+
+    /// Write raw bytes, e.g. binary protocol frames
+    pub async fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), W::Error> {
+        self.writer.write_all(bytes).await?;
+        self.written = self.written.wrapping_add(bytes.len());
+        if self.auto_flush {
+            self.writer.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Get the number of bytes written since creation or the last
+    /// [`reset_counter`](Self::reset_counter), wrapping on overflow
+    ///
+    /// Escape codes count as well, as everything goes through
+    /// [`write_bytes`](Self::write_bytes).
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
+
+    /// Restart [`bytes_written`](Self::bytes_written) from zero
+    pub fn reset_counter(&mut self) {
+        self.written = 0;
+    }
+
     /// Write a string
     pub async fn write_str(&mut self, s: &str) -> Result<(), W::Error> {
-        self.writer.write_all(s.as_bytes()).await?;
-        self.writer.flush().await
+        match self.wrap_width {
+            Some(width) if width > 0 => self.write_wrapped(s, width as usize).await,
+            _ => self.write_bytes(s.as_bytes()).await,
+        }
+    }
+
+    /// Write a string, breaking the line before the column passes `width`
+    async fn write_wrapped(&mut self, s: &str, width: usize) -> Result<(), W::Error> {
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            if self.escape != Escape::None {
+                self.escape = match (self.escape, c) {
+                    (Escape::Start, '[') => Escape::Csi,
+                    (Escape::Start, ']') => Escape::Osc,
+                    (Escape::Csi, '@'..='~') | (Escape::Osc, '\x07') => Escape::None,
+                    (Escape::Osc, '\x1b') => Escape::Start,
+                    (Escape::Start, _) => Escape::None,
+                    (state, _) => state,
+                };
+                continue;
+            }
+            match c {
+                '\r' | '\n' => self.column = 0,
+                '\x1b' => self.escape = Escape::Start,
+                c => {
+                    let c_width = char_width(c);
+                    if c_width > 0 && self.column + c_width > width {
+                        self.write_bytes(&s.as_bytes()[start..i]).await?;
+                        self.write_bytes(b"\r\n").await?;
+                        start = i;
+                        self.column = 0;
+                    }
+                    self.column += c_width;
+                }
+            }
+        }
+        self.write_bytes(&s.as_bytes()[start..]).await
     }
 
     /// Write a formatted string
+    ///
+    /// The output is formatted into a 256 byte scratch buffer before being
+    /// written. If formatting fails or the output does not fit, nothing is
+    /// written and [`WriteFmtError::Format`] is returned. Use
+    /// [`TerminalWriter::write_fmt_buf`] for longer output.
     pub async fn write_fmt(
         &mut self,
         args: fmt::Arguments<'_>,
-    ) -> Result<(), W::Error> {
+    ) -> Result<(), WriteFmtError<W::Error>> {
+        self.write_fmt_buf::<256>(args).await
+    }
+
+    /// Write a formatted string using an `N` byte scratch buffer
+    pub async fn write_fmt_buf<const N: usize>(
+        &mut self,
+        args: fmt::Arguments<'_>,
+    ) -> Result<(), WriteFmtError<W::Error>> {
         // For no_std, we need to format to a temporary buffer
         use heapless::String;
-        let mut buffer = String::<256>::new();
-        fmt::write(&mut buffer, args).map_err(|_| embedded_io_async::ErrorKind::Other);
-        self.write_str(&buffer).await
+        let mut buffer = String::<N>::new();
+        fmt::write(&mut buffer, args).map_err(|_| WriteFmtError::Format)?;
+        self.write_str(&buffer).await.map_err(WriteFmtError::Io)
     }
 
     /// Write a line (adds \r\n)
@@ -56,6 +226,41 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         }
     }
 
+    /// Clear the current line from a column (1-based) to its end
+    ///
+    /// Text before `col` is kept. Without ANSI support this falls back to
+    /// [`clear_line`](Self::clear_line), which always returns to column 1.
+    pub async fn clear_line_from(&mut self, col: u16) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.move_cursor_col(col).await?;
+            self.write_str("\x1b[K").await
+        } else {
+            self.clear_line().await
+        }
+    }
+
+    /// Clear from the cursor to the end of the screen (`ESC[0J`)
+    ///
+    /// Does nothing without ANSI support.
+    pub async fn clear_to_end(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b[0J").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Clear from the start of the screen to the cursor (`ESC[1J`)
+    ///
+    /// Does nothing without ANSI support.
+    pub async fn clear_to_start(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b[1J").await
+        } else {
+            Ok(())
+        }
+    }
+
     /// Clear the screen
     pub async fn clear_screen(&mut self) -> Result<(), W::Error> {
         if self.ansi_enabled {
@@ -92,6 +297,161 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         }
     }
 
+    /// Insert n blank lines at the cursor, pushing the lines below down
+    pub async fn insert_lines(&mut self, n: usize) -> Result<(), W::Error> {
+        if self.ansi_enabled && n > 0 {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{}L", n).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete n lines at the cursor, pulling the lines below up
+    pub async fn delete_lines(&mut self, n: usize) -> Result<(), W::Error> {
+        if self.ansi_enabled && n > 0 {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{}M", n).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Restrict scrolling to the rows from `top` to `bottom` (1-based, inclusive)
+    ///
+    /// Lines written past `bottom` scroll only this region, e.g. under a fixed
+    /// header. Terminals move the cursor to the top left corner.
+    pub async fn set_scroll_region(&mut self, top: u16, bottom: u16) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{};{}r", top, bottom).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Let the whole screen scroll again by writing `ESC[r`
+    pub async fn reset_scroll_region(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b[r").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Scroll the contents up by n lines, adding blank lines at the bottom
+    pub async fn scroll_up(&mut self, n: usize) -> Result<(), W::Error> {
+        if self.ansi_enabled && n > 0 {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{}S", n).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Scroll the contents down by n lines, adding blank lines at the top
+    pub async fn scroll_down(&mut self, n: usize) -> Result<(), W::Error> {
+        if self.ansi_enabled && n > 0 {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{}T", n).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move the cursor to an absolute position (1-based row and column)
+    pub async fn move_cursor_to(&mut self, row: u16, col: u16) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{};{}H", row, col).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move the cursor to a column of the current line (1-based)
+    pub async fn move_cursor_col(&mut self, col: u16) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[{}G", col).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Save the cursor position
+    ///
+    /// Uses the DEC `ESC 7` sequence rather than the SCO `ESC[s`, since the
+    /// latter is reinterpreted by terminals that support left/right margins.
+    pub async fn save_cursor(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b7").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Restore the cursor position saved by [`TerminalWriter::save_cursor`]
+    ///
+    /// Uses the DEC `ESC 8` sequence to pair with `save_cursor`.
+    pub async fn restore_cursor(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b8").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Hide the cursor
+    pub async fn hide_cursor(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b[?25l").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Show the cursor
+    pub async fn show_cursor(&mut self) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b[?25h").await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set the terminal window or tab title
+    pub async fn set_title(&mut self, title: &str) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            self.write_str("\x1b]0;").await?;
+            self.write_str(title).await?;
+            self.write_str("\x07").await
+        } else {
+            Ok(())
+        }
+    }
+
     /// Set text color (ANSI colors: 0-7 for basic colors, 8-15 for bright colors)
     pub async fn set_color(&mut self, color: u8) -> Result<(), W::Error> {
         if self.ansi_enabled {
@@ -109,6 +469,75 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         }
     }
 
+    /// Set background color (ANSI colors: 0-7 for basic colors, 8-15 for bright colors)
+    pub async fn set_bg_color(&mut self, color: u8) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            if color < 8 {
+                write!(&mut cmd, "\x1b[4{}m", color).ok();
+            } else {
+                write!(&mut cmd, "\x1b[10{}m", color - 8).ok();
+            }
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set text color from the 256-color palette
+    pub async fn set_color_256(&mut self, idx: u8) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[38;5;{}m", idx).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set text color as a 24-bit RGB value
+    pub async fn set_rgb(&mut self, r: u8, g: u8, b: u8) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<24>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[38;2;{};{};{}m", r, g, b).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set background color from the 256-color palette
+    pub async fn set_bg_256(&mut self, idx: u8) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<16>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[48;5;{}m", idx).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set background color as a 24-bit RGB value
+    pub async fn set_bg_rgb(&mut self, r: u8, g: u8, b: u8) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            use heapless::String;
+            let mut cmd = String::<24>::new();
+            use core::fmt::Write;
+            write!(&mut cmd, "\x1b[48;2;{};{};{}m", r, g, b).ok();
+            self.write_str(&cmd).await
+        } else {
+            Ok(())
+        }
+    }
+
     /// Reset text formatting
     pub async fn reset_format(&mut self) -> Result<(), W::Error> {
         if self.ansi_enabled {
@@ -131,6 +560,58 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         }
     }
 
+    /// Set underlined text
+    pub async fn set_underline(&mut self, enable: bool) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            if enable {
+                self.write_str("\x1b[4m").await
+            } else {
+                self.write_str("\x1b[24m").await
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set italic text
+    pub async fn set_italic(&mut self, enable: bool) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            if enable {
+                self.write_str("\x1b[3m").await
+            } else {
+                self.write_str("\x1b[23m").await
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set strikethrough text
+    pub async fn set_strikethrough(&mut self, enable: bool) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            if enable {
+                self.write_str("\x1b[9m").await
+            } else {
+                self.write_str("\x1b[29m").await
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set reverse video, swapping foreground and background colors
+    pub async fn set_reverse(&mut self, enable: bool) -> Result<(), W::Error> {
+        if self.ansi_enabled {
+            if enable {
+                self.write_str("\x1b[7m").await
+            } else {
+                self.write_str("\x1b[27m").await
+            }
+        } else {
+            Ok(())
+        }
+    }
+
     /// Write colored text
     pub async fn write_colored(
         &mut self,
@@ -142,46 +623,364 @@ impl<'a, W: AsyncWrite> TerminalWriter<'a, W> {
         self.reset_format().await
     }
 
+    /// Write text with both foreground and background colors
+    pub async fn write_colored_bg(
+        &mut self,
+        text: &str,
+        fg: u8,
+        bg: u8,
+    ) -> Result<(), W::Error> {
+        self.set_color(fg).await?;
+        self.set_bg_color(bg).await?;
+        self.write_str(text).await?;
+        self.reset_format().await
+    }
+
     /// Write an error message
     pub async fn write_error(&mut self, msg: &str) -> Result<(), W::Error> {
-        if self.ansi_enabled {
-            self.write_colored(msg, 1).await // Red
-        } else {
-            self.writeln(msg).await
-        }
+        self.write_level(msg, self.theme.error).await
     }
 
     /// Write a success message
     pub async fn write_success(&mut self, msg: &str) -> Result<(), W::Error> {
-        if self.ansi_enabled {
-            self.write_colored(msg, 2).await // Green
-        } else {
-            self.writeln(msg).await
-        }
+        self.write_level(msg, self.theme.success).await
     }
 
     /// Write a warning message
     pub async fn write_warning(&mut self, msg: &str) -> Result<(), W::Error> {
-        if self.ansi_enabled {
-            self.write_colored(msg, 3).await // Yellow
-        } else {
-            self.writeln(msg).await
+        self.write_level(msg, self.theme.warning).await
+    }
+
+    /// Write a message in a theme color, or as a line without ANSI support
+    async fn write_level(&mut self, msg: &str, color: Option<u8>) -> Result<(), W::Error> {
+        match color {
+            _ if !self.ansi_enabled => self.writeln(msg).await,
+            Some(color) => self.write_colored(msg, color).await,
+            None => self.write_str(msg).await,
         }
     }
 
     /// Write an info message
     pub async fn write_info(&mut self, msg: &str) -> Result<(), W::Error> {
+        self.write_level(msg, self.theme.info).await
+    }
+
+    /// List completion candidates below the input, then redraw it
+    ///
+    /// Candidates are laid out in aligned columns as wide as the longest
+    /// candidate plus two spaces, using as many columns as fit into `width`.
+    /// The prompt and `input` are written again on the line below the list.
+    pub async fn write_completions(
+        &mut self,
+        candidates: &[&str],
+        width: u16,
+        prompt: &str,
+        input: &str,
+    ) -> Result<(), W::Error> {
+        self.write_str("\r\n").await?;
+        completion::write_candidates(self, candidates, CompletionLayout::Grid, Some(width)).await?;
+        self.write_prompt(prompt).await?;
+        self.write_str(input).await
+    }
+
+    /// Write `text` as a clickable link to `url` (OSC 8)
+    ///
+    /// Without ANSI support the link is written as `text (url)`.
+    pub async fn write_hyperlink(&mut self, url: &str, text: &str) -> Result<(), W::Error> {
         if self.ansi_enabled {
-            self.write_colored(msg, 6).await // Cyan
+            self.write_str("\x1b]8;;").await?;
+            self.write_str(url).await?;
+            self.write_str("\x1b\\").await?;
+            self.write_str(text).await?;
+            self.write_str("\x1b]8;;\x1b\\").await
         } else {
-            self.writeln(msg).await
+            self.write_str(text).await?;
+            self.write_str(" (").await?;
+            self.write_str(url).await?;
+            self.write_str(")").await
+        }
+    }
+
+    /// Draw a progress bar like `label [#####-----]  50%` over the current line
+    ///
+    /// The bar starts with `\r`, so calling this repeatedly updates it in
+    /// place. `fraction` is clamped to `0.0..=1.0`. With ANSI enabled the
+    /// filled part is green; otherwise only ASCII characters are written.
+    /// Finish with [`TerminalWriter::progress_done`].
+    pub async fn write_progress(&mut self, label: &str, fraction: f32) -> Result<(), W::Error> {
+        const FILLED: &str = "####################";
+        const EMPTY: &str = "--------------------";
+
+        let fraction = if fraction.is_nan() { 0.0 } else { fraction.clamp(0.0, 1.0) };
+        let filled = (fraction * PROGRESS_WIDTH as f32) as usize;
+        let percent = (fraction * 100.0) as u32;
+
+        self.write_str("\r").await?;
+        self.write_str(label).await?;
+        self.write_str(" [").await?;
+        if filled > 0 {
+            self.write_colored(&FILLED[..filled], colors::GREEN).await?;
         }
+        self.write_str(&EMPTY[filled..]).await?;
+
+        let mut tail = heapless::String::<8>::new();
+        // At most "] 100%", which always fits
+        let _ = fmt::write(&mut tail, format_args!("] {:>3}%", percent));
+        self.write_str(&tail).await
+    }
+
+    /// Finish a progress bar so later output starts on a fresh line
+    pub async fn progress_done(&mut self) -> Result<(), W::Error> {
+        self.write_str("\r\n").await
+    }
+
+    /// Draw a table with a header row, each column as wide as its widest cell
+    ///
+    /// The number of columns is taken from `headers` and capped at
+    /// [`MAX_TABLE_COLUMNS`]; extra cells are ignored and missing cells are
+    /// left empty. With ANSI enabled the table is framed with box-drawing
+    /// characters, otherwise columns are separated by two spaces and the
+    /// header is underlined with `-`.
+    pub async fn write_table(
+        &mut self,
+        headers: &[&str],
+        rows: &[&[&str]],
+    ) -> Result<(), W::Error> {
+        let columns = headers.len().min(MAX_TABLE_COLUMNS);
+        let mut widths = [0usize; MAX_TABLE_COLUMNS];
+        for row in core::iter::once(headers).chain(rows.iter().copied()) {
+            for (width, cell) in widths.iter_mut().zip(row.iter().take(columns)) {
+                *width = (*width).max(display_width(cell));
+            }
+        }
+        let widths = &widths[..columns];
+
+        if self.ansi_enabled {
+            self.write_table_rule(widths, ["┌", "┬", "┐"]).await?;
+            self.write_table_row(widths, headers).await?;
+            self.write_table_rule(widths, ["├", "┼", "┤"]).await?;
+            for row in rows {
+                self.write_table_row(widths, row).await?;
+            }
+            self.write_table_rule(widths, ["└", "┴", "┘"]).await
+        } else {
+            self.write_table_row(widths, headers).await?;
+            for (i, width) in widths.iter().enumerate() {
+                if i > 0 {
+                    self.write_str("  ").await?;
+                }
+                self.write_repeated("-", *width).await?;
+            }
+            self.write_str("\r\n").await?;
+            for row in rows {
+                self.write_table_row(widths, row).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Write one table row, padding each cell to its column width
+    async fn write_table_row(&mut self, widths: &[usize], row: &[&str]) -> Result<(), W::Error> {
+        let boxed = self.ansi_enabled;
+        if boxed {
+            self.write_str("│ ").await?;
+        }
+        // Plain rows stop after the last non-empty cell, leaving no trailing spaces
+        let columns = if boxed {
+            widths.len()
+        } else {
+            row.iter()
+                .take(widths.len())
+                .rposition(|cell| !cell.is_empty())
+                .map_or(0, |i| i + 1)
+        };
+        for (i, width) in widths[..columns].iter().enumerate() {
+            let cell = row.get(i).copied().unwrap_or("");
+            let last = i + 1 == columns;
+            self.write_str(cell).await?;
+            if boxed || !last {
+                self.write_repeated(" ", width - display_width(cell)).await?;
+            }
+            if !last {
+                self.write_str(if boxed { " │ " } else { "  " }).await?;
+            }
+        }
+        if boxed {
+            self.write_str(" │").await?;
+        }
+        self.write_str("\r\n").await
+    }
+
+    /// Write a horizontal box-drawing rule from left, junction and right corners
+    async fn write_table_rule(
+        &mut self,
+        widths: &[usize],
+        corners: [&str; 3],
+    ) -> Result<(), W::Error> {
+        self.write_str(corners[0]).await?;
+        for (i, width) in widths.iter().enumerate() {
+            if i > 0 {
+                self.write_str(corners[1]).await?;
+            }
+            self.write_repeated("─", width + 2).await?;
+        }
+        self.write_str(corners[2]).await?;
+        self.write_str("\r\n").await
+    }
+
+    /// Write `s` `n` times, batching the copies into few writes
+    ///
+    /// `s` must be a non-empty string of at most 48 bytes.
+    async fn write_repeated(&mut self, s: &str, n: usize) -> Result<(), W::Error> {
+        let mut chunk = [0u8; 48];
+        let mut copies = 0;
+        for slot in chunk.chunks_exact_mut(s.len()) {
+            slot.copy_from_slice(s.as_bytes());
+            copies += 1;
+        }
+        // Whole copies of `s` are always valid UTF-8
+        let chunk = core::str::from_utf8(&chunk[..copies * s.len()]).unwrap_or("");
+        let mut left = n;
+        while left > 0 {
+            let count = left.min(copies);
+            self.write_str(&chunk[..count * s.len()]).await?;
+            left -= count;
+        }
+        Ok(())
+    }
+
+    /// Ring the terminal bell
+    ///
+    /// BEL is a plain control character, so it is sent even when ANSI
+    /// escape codes are disabled.
+    pub async fn bell(&mut self) -> Result<(), W::Error> {
+        self.write_str("\x07").await
     }
 
     /// Flush the writer
     pub async fn flush(&mut self) -> Result<(), W::Error> {
         self.writer.flush().await
     }
+
+    /// Start a line of differently styled pieces, written by
+    /// [`Segments::write`]
+    pub fn segments<'s>(&mut self) -> Segments<'_, 'a, 's, W> {
+        Segments {
+            writer: self,
+            segments: heapless::Vec::new(),
+            truncated: false,
+        }
+    }
+}
+
+/// Builder for a line of styled text, see [`TerminalWriter::segments`]
+///
+/// Up to [`MAX_SEGMENTS`] pieces are queued; further ones are dropped, which
+/// [`Segments::is_truncated`] reports before the line is written and
+/// [`Segments::write`] reports as an error.
+pub struct Segments<'w, 'a, 's, W: AsyncWrite> {
+    writer: &'w mut TerminalWriter<'a, W>,
+    segments: heapless::Vec<(&'s str, Style), MAX_SEGMENTS>,
+    /// A piece was dropped because the queue was full
+    truncated: bool,
+}
+
+/// Formatting of one queued segment
+#[derive(Debug, Clone, Copy)]
+enum Style {
+    Plain,
+    Color(u8),
+    Bold,
+}
+
+impl<'s, W: AsyncWrite> Segments<'_, '_, 's, W> {
+    /// Queue unformatted text
+    pub fn text(self, text: &'s str) -> Self {
+        self.push(text, Style::Plain)
+    }
+
+    /// Queue text in one of the [`colors`]
+    pub fn colored(self, text: &'s str, color: u8) -> Self {
+        self.push(text, Style::Color(color))
+    }
+
+    /// Queue bold text
+    pub fn bold(self, text: &'s str) -> Self {
+        self.push(text, Style::Bold)
+    }
+
+    /// Check whether pieces were dropped because more than [`MAX_SEGMENTS`]
+    /// were queued
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    fn push(mut self, text: &'s str, style: Style) -> Self {
+        if self.segments.push((text, style)).is_err() {
+            self.truncated = true;
+        }
+        self
+    }
+
+    /// Write the queued segments in order
+    ///
+    /// Each styled segment is followed by a reset, so attributes never carry
+    /// over to the next one. If writing fails a final reset is attempted
+    /// before the error is returned.
+    ///
+    /// If pieces were dropped, the queued ones are still written and
+    /// [`WriteFmtError::Format`] is returned afterwards.
+    pub async fn write(self) -> Result<(), WriteFmtError<W::Error>> {
+        let writer = self.writer;
+        for &(text, style) in &self.segments {
+            if let Err(err) = write_segment(writer, text, style).await {
+                let _ = writer.reset_format().await;
+                return Err(WriteFmtError::Io(err));
+            }
+        }
+        if self.truncated {
+            return Err(WriteFmtError::Format);
+        }
+        Ok(())
+    }
+}
+
+async fn write_segment<W: AsyncWrite>(
+    writer: &mut TerminalWriter<'_, W>,
+    text: &str,
+    style: Style,
+) -> Result<(), W::Error> {
+    match style {
+        Style::Plain => writer.write_str(text).await,
+        Style::Color(color) => writer.write_colored(text, color).await,
+        Style::Bold => {
+            writer.set_bold(true).await?;
+            writer.write_str(text).await?;
+            writer.reset_format().await
+        }
+    }
+}
+
+/// Position within an escape sequence while wrapping output
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    /// After `ESC`
+    Start,
+    /// Within `ESC [`, ended by a byte in `@`..=`~`
+    Csi,
+    /// Within `ESC ]`, ended by BEL or `ESC \`
+    Osc,
+}
+
+/// Errors that can occur while writing formatted output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WriteFmtError<E> {
+    /// The underlying writer returned an error
+    Io(E),
+    /// Formatting failed, e.g. the output overflowed the scratch buffer or
+    /// [`Segments`] dropped pieces
+    Format,
 }
 
 /// ANSI color codes for convenience
@@ -203,4 +1002,436 @@ pub mod colors {
     pub const BRIGHT_MAGENTA: u8 = 13;
     pub const BRIGHT_CYAN: u8 = 14;
     pub const BRIGHT_WHITE: u8 = 15;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::MockWriter;
+    use embassy_futures::block_on;
+
+    #[test]
+    fn test_write_fmt() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.write_fmt(format_args!("value={}", 42))).unwrap();
+        assert_eq!(mock.as_str(), "value=42");
+    }
+
+    #[test]
+    fn test_write_fmt_overflow_is_reported() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let long = [b'x'; 300];
+        let long = core::str::from_utf8(&long).unwrap();
+        let result = block_on(writer.write_fmt(format_args!("{}", long)));
+        assert_eq!(result, Err(WriteFmtError::Format));
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_write_fmt_buf_long_output() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let long = [b'x'; 300];
+        let long = core::str::from_utf8(&long).unwrap();
+        block_on(writer.write_fmt_buf::<512>(format_args!("{}!", long))).unwrap();
+        assert_eq!(mock.data.len(), 301);
+        assert!(mock.as_str().ends_with("x!"));
+    }
+
+    #[test]
+    fn test_extended_colors() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.set_color_256(208)).unwrap();
+        block_on(writer.set_rgb(255, 128, 0)).unwrap();
+        block_on(writer.set_bg_256(17)).unwrap();
+        block_on(writer.set_bg_rgb(0, 0, 64)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            "\x1b[38;5;208m\x1b[38;2;255;128;0m\x1b[48;5;17m\x1b[48;2;0;0;64m"
+        );
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.set_color_256(208)).unwrap();
+        block_on(writer.set_rgb(255, 128, 0)).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_write_colored_bg() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_colored_bg("OK", colors::BLACK, colors::BRIGHT_GREEN)).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[30m\x1b[102mOK\x1b[0m");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.write_colored_bg("OK", colors::BLACK, colors::GREEN)).unwrap();
+        assert_eq!(mock.as_str(), "OK");
+    }
+
+    #[test]
+    fn test_text_attributes() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.set_underline(true)).unwrap();
+        block_on(writer.set_italic(true)).unwrap();
+        block_on(writer.set_strikethrough(true)).unwrap();
+        block_on(writer.set_reverse(true)).unwrap();
+        block_on(writer.set_underline(false)).unwrap();
+        block_on(writer.set_italic(false)).unwrap();
+        block_on(writer.set_strikethrough(false)).unwrap();
+        block_on(writer.set_reverse(false)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            "\x1b[4m\x1b[3m\x1b[9m\x1b[7m\x1b[24m\x1b[23m\x1b[29m\x1b[27m"
+        );
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.set_underline(true)).unwrap();
+        block_on(writer.set_italic(true)).unwrap();
+        block_on(writer.set_strikethrough(true)).unwrap();
+        block_on(writer.set_reverse(true)).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_save_restore_cursor() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.save_cursor()).unwrap();
+        block_on(writer.restore_cursor()).unwrap();
+        assert_eq!(mock.as_str(), "\x1b7\x1b8");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.save_cursor()).unwrap();
+        block_on(writer.restore_cursor()).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_clear_line_from() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.clear_line_from(7)).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[7G\x1b[K");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.clear_line_from(7)).unwrap();
+        assert_eq!(mock.as_str(), "\r");
+    }
+
+    #[test]
+    fn test_clear_to_end_and_start() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.clear_to_end()).unwrap();
+        block_on(writer.clear_to_start()).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[0J\x1b[1J");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.clear_to_end()).unwrap();
+        block_on(writer.clear_to_start()).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_insert_and_delete_lines() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.insert_lines(2)).unwrap();
+        block_on(writer.delete_lines(3)).unwrap();
+        block_on(writer.insert_lines(0)).unwrap();
+        block_on(writer.delete_lines(0)).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[2L\x1b[3M");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.insert_lines(2)).unwrap();
+        block_on(writer.delete_lines(3)).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_scroll_controls() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.set_scroll_region(2, 24)).unwrap();
+        block_on(writer.scroll_up(1)).unwrap();
+        block_on(writer.scroll_down(3)).unwrap();
+        block_on(writer.scroll_up(0)).unwrap();
+        block_on(writer.reset_scroll_region()).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[2;24r\x1b[1S\x1b[3T\x1b[r");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.set_scroll_region(2, 24)).unwrap();
+        block_on(writer.scroll_up(1)).unwrap();
+        block_on(writer.reset_scroll_region()).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_segments() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        let line = writer
+            .segments()
+            .text("wifi: ")
+            .colored("up", colors::GREEN)
+            .text(" ")
+            .bold("3 peers");
+        block_on(line.write()).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            "wifi: \x1b[32mup\x1b[0m \x1b[1m3 peers\x1b[0m"
+        );
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let line = writer.segments().colored("up", colors::GREEN).bold("!");
+        block_on(line.write()).unwrap();
+        assert_eq!(mock.as_str(), "up!");
+    }
+
+    #[test]
+    fn test_segments_overflow_drops_extra_pieces() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let mut line = writer.segments();
+        for _ in 0..MAX_SEGMENTS {
+            line = line.text("x");
+        }
+        assert!(!line.is_truncated());
+        let line = line.bold("dropped").text("dropped");
+        assert!(line.is_truncated());
+        assert_eq!(block_on(line.write()), Err(WriteFmtError::Format));
+        assert_eq!(mock.as_str(), "xxxxxxxxxxxxxxxx");
+    }
+
+    #[test]
+    fn test_theme_colors() {
+        let theme = Theme {
+            error: Some(colors::BRIGHT_RED),
+            info: None,
+            ..Theme::default()
+        };
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true).with_theme(theme);
+        block_on(writer.write_error("e")).unwrap();
+        block_on(writer.write_success("s")).unwrap();
+        block_on(writer.write_info("i")).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[91me\x1b[0m\x1b[32ms\x1b[0mi");
+    }
+
+    #[test]
+    fn test_write_completions() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let candidates = ["help", "hello", "history"];
+        block_on(writer.write_completions(&candidates, 20, "> ", "h")).unwrap();
+        assert_eq!(mock.as_str(), "\r\nhelp     hello\r\nhistory\r\n> h");
+    }
+
+    #[test]
+    fn test_absolute_cursor_movement() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.move_cursor_to(5, 10)).unwrap();
+        block_on(writer.move_cursor_col(3)).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[5;10H\x1b[3G");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.move_cursor_to(5, 10)).unwrap();
+        block_on(writer.move_cursor_col(3)).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_hide_show_cursor() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.hide_cursor()).unwrap();
+        block_on(writer.show_cursor()).unwrap();
+        assert_eq!(mock.as_str(), "\x1b[?25l\x1b[?25h");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.hide_cursor()).unwrap();
+        block_on(writer.show_cursor()).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_write_progress_ascii() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.write_progress("flash", 0.5)).unwrap();
+        block_on(writer.write_progress("flash", 1.5)).unwrap();
+        block_on(writer.progress_done()).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            concat!(
+                "\rflash [##########----------]  50%",
+                "\rflash [####################] 100%",
+                "\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_progress_ansi() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_progress("erase", -1.0)).unwrap();
+        block_on(writer.write_progress("erase", 0.25)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            concat!(
+                "\rerase [--------------------]   0%",
+                "\rerase [\x1b[32m#####\x1b[0m---------------]  25%",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_table_plain() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let rows: &[&[&str]] = &[&["wifi", "up", "ignored"], &["bluetooth"]];
+        block_on(writer.write_table(&["name", "state"], rows)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            concat!(
+                "name       state\r\n",
+                "---------  -----\r\n",
+                "wifi       up\r\n",
+                "bluetooth\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_table_plain_skips_empty_trailing_cells() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        let rows: &[&[&str]] = &[&["a", "", ""], &["", "", ""]];
+        block_on(writer.write_table(&["key", "value", "unit"], rows)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            concat!("key  value  unit\r\n", "---  -----  ----\r\n", "a\r\n", "\r\n")
+        );
+    }
+
+    #[test]
+    fn test_write_repeated_batches_writes() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_repeated(" ", 50)).unwrap();
+        block_on(writer.write_repeated("─", 20)).unwrap();
+        assert_eq!(mock.writes, 4);
+        let mut expected = heapless::String::<128>::new();
+        for _ in 0..50 {
+            expected.push(' ').unwrap();
+        }
+        for _ in 0..20 {
+            expected.push('─').unwrap();
+        }
+        assert_eq!(mock.as_str(), expected.as_str());
+    }
+
+    #[test]
+    fn test_write_table_boxed() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        let rows: &[&[&str]] = &[&["wifi", "up"]];
+        block_on(writer.write_table(&["name", "state"], rows)).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            concat!(
+                "┌──────┬───────┐\r\n",
+                "│ name │ state │\r\n",
+                "├──────┼───────┤\r\n",
+                "│ wifi │ up    │\r\n",
+                "└──────┴───────┘\r\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_write_hyperlink() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_hyperlink("https://docs.rs", "docs")).unwrap();
+        assert_eq!(mock.as_str(), "\x1b]8;;https://docs.rs\x1b\\docs\x1b]8;;\x1b\\");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.write_hyperlink("https://docs.rs", "docs")).unwrap();
+        assert_eq!(mock.as_str(), "docs (https://docs.rs)");
+    }
+
+    #[test]
+    fn test_set_title() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.set_title("console 2")).unwrap();
+        assert_eq!(mock.as_str(), "\x1b]0;console 2\x07");
+
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false);
+        block_on(writer.set_title("console 2")).unwrap();
+        assert_eq!(mock.as_str(), "");
+    }
+
+    #[test]
+    fn test_write_bytes() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_bytes(&[0x7e, 0x00, 0xff, 0x7e])).unwrap();
+        assert_eq!(mock.data.as_slice(), &[0x7e, 0x00, 0xff, 0x7e]);
+    }
+
+    #[test]
+    fn test_bytes_written() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true);
+        block_on(writer.write_str("abc")).unwrap();
+        block_on(writer.write_bytes(&[0, 1])).unwrap();
+        block_on(writer.clear_line()).unwrap();
+        assert_eq!(writer.bytes_written(), 9);
+        writer.reset_counter();
+        assert_eq!(writer.bytes_written(), 0);
+    }
+
+    #[test]
+    fn test_with_width_wraps_output() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, false).with_width(4);
+        block_on(writer.write_str("abcdef")).unwrap();
+        block_on(writer.write_str("gh\r\nij")).unwrap();
+        block_on(writer.write_str("klm")).unwrap();
+        assert_eq!(mock.as_str(), "abcd\r\nefgh\r\nijkl\r\nm");
+    }
+
+    #[test]
+    fn test_with_width_skips_escape_sequences() {
+        let mut mock = MockWriter::new();
+        let mut writer = TerminalWriter::new(&mut mock, true).with_width(3);
+        block_on(writer.write_colored("abcd", colors::RED)).unwrap();
+        block_on(writer.write_str("中文")).unwrap();
+        block_on(writer.write_hyperlink("https://x", "ab")).unwrap();
+        assert_eq!(
+            mock.as_str(),
+            "\x1b[31mabc\r\nd\x1b[0m中\r\n文\x1b]8;;https://x\x1b\\a\r\nb\x1b]8;;\x1b\\"
+        );
+    }
+}